@@ -5,4 +5,23 @@ set -e -u
 unit_operation="${1:?}"
 unit_path="${2:?}"
 
+case "$unit_operation" in
+  journal-append)
+    mkdir -p "$(dirname "$unit_path")"
+    exec cat >> "$unit_path";;
+  journal-read)
+    if [ -e "$unit_path" ]; then exec cat "$unit_path"; fi
+    exit 0;;
+esac
+
+if [ -d "$unit_path" ]; then
+  cd "$unit_path"
+
+  if [ "$unit_operation" = "test" ]; then
+    exec ./test
+  fi
+
+  exec ./unit "$unit_operation"
+fi
+
 exec "$unit_path" "$unit_operation"
@@ -0,0 +1,330 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::doc::Format as DocFormat;
+use crate::engine::{self, Engine, FleetSummary, Options, Summary};
+use crate::error::Error;
+use crate::events::{EventSink, Silent};
+use crate::execution::Execution;
+use crate::graph::Format as GraphFormat;
+use crate::inventory::{Inventory, TargetSpec};
+use crate::journal;
+use crate::resolver::InstanceVec;
+use crate::operation::Operation;
+use crate::ui::{self, Mode};
+use crate::unit::{ApplicationState, ArgSet, Instance, InstanceId, Outcome, RetryPolicy};
+
+/// Configures a run of sysunit.  Nothing is printed unless a reporting mode or
+/// event sink is given, results being returned instead:
+///
+/// ```no_run
+/// use sysunit::{Builder, Operation};
+///
+/// let plan = Builder::new(Operation::Apply)
+///     .unit_paths(vec!["./units".to_string()])
+///     .target("ssh://root@web1")
+///     .unit("nginx:port=80")
+///     .resolve()?;
+///
+/// let report = plan.run()?;
+///
+/// for instance in report.instances.iter() {
+///     println!("{}: {:?}", instance.id, instance.outcome);
+/// }
+/// # Ok::<(), sysunit::Error>(())
+/// ```
+///
+/// A plan is made against a single target.  To operate upon several, as the
+/// command line does, use [`Builder::run`] instead.
+pub struct Builder {
+    operation: Operation,
+    units: Vec<String>,
+    target_urls: Vec<String>,
+    /// Path of an inventory and the group or host selected from it
+    inventory: Option<(String, String)>,
+    adapter: Option<String>,
+    default_args: ArgSet,
+    options: Options,
+    events: Events,
+}
+
+/// Where a run's progress goes
+enum Events {
+    Silent,
+    Mode(Mode),
+    Sink(Arc<dyn EventSink>),
+}
+
+impl Builder {
+    pub fn new(operation: Operation) -> Builder {
+        Builder {
+            operation,
+            units: Vec::new(),
+            target_urls: Vec::new(),
+            inventory: None,
+            adapter: None,
+            default_args: ArgSet::new(),
+            options: Options::default(),
+            events: Events::Silent,
+        }
+    }
+
+    /// Adds a root unit, in `name[:key=value,...]` form
+    pub fn unit(mut self, spec: &str) -> Builder {
+        self.units.push(spec.to_string());
+        self
+    }
+
+    /// Replaces the directories units are looked for in, which otherwise come
+    /// from SYSUNIT_PATH
+    pub fn unit_paths(mut self, unit_paths: Vec<String>) -> Builder {
+        self.options.unit_paths = unit_paths;
+        self
+    }
+
+    /// Operates on the target at the given URL rather than the local machine.
+    /// May be given several times.
+    pub fn target(mut self, url: &str) -> Builder {
+        self.target_urls.push(url.to_string());
+        self
+    }
+
+    /// Operates on the hosts of a group, or a single host, from a TOML
+    /// inventory rather than on targets given by URL
+    pub fn inventory(mut self, path: &str, selection: &str) -> Builder {
+        self.inventory = Some((path.to_string(), selection.to_string()));
+        self
+    }
+
+    /// Executes units through an adapter, on targets which don't name one
+    pub fn adapter(mut self, adapter: &str) -> Builder {
+        self.adapter = Some(adapter.to_string());
+        self
+    }
+
+    /// Arguments given to every root unit which doesn't set them itself
+    pub fn default_args(mut self, args: ArgSet) -> Builder {
+        self.default_args = args;
+        self
+    }
+
+    pub fn jobs(mut self, jobs: usize) -> Builder {
+        self.options.jobs = jobs;
+        self
+    }
+
+    /// Number of targets operated upon at once by [`Builder::run`]
+    pub fn parallel_hosts(mut self, parallel_hosts: usize) -> Builder {
+        self.options.parallel_hosts = parallel_hosts;
+        self
+    }
+
+    pub fn fail_fast(mut self, fail_fast: bool) -> Builder {
+        self.options.fail_fast = fail_fast;
+        self
+    }
+
+    pub fn atomic(mut self, atomic: bool) -> Builder {
+        self.options.atomic = atomic;
+        self
+    }
+
+    pub fn root_only(mut self, root_only: bool) -> Builder {
+        self.options.root_only = root_only;
+        self
+    }
+
+    /// Makes a plan describe a rollback rather than an apply
+    pub fn plan_rollback(mut self, plan_rollback: bool) -> Builder {
+        self.options.plan_rollback = plan_rollback;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Builder {
+        self.options.timeout = timeout;
+        self
+    }
+
+    pub fn retry(mut self, retry: RetryPolicy) -> Builder {
+        self.options.retry = retry;
+        self
+    }
+
+    pub fn journal(mut self, path: Option<String>) -> Builder {
+        self.options.journal = path;
+        self
+    }
+
+    /// Also writes a JUnit XML report of the run to the given path
+    pub fn report_file(mut self, path: Option<String>) -> Builder {
+        self.options.report_file = path;
+        self
+    }
+
+    pub fn doc_format(mut self, format: DocFormat) -> Builder {
+        self.options.doc_format = format;
+        self
+    }
+
+    pub fn graph_format(mut self, format: GraphFormat) -> Builder {
+        self.options.graph_format = format;
+        self
+    }
+
+    /// Makes `graph` check each unit, to colour nodes by the result
+    pub fn check_state(mut self, check_state: bool) -> Builder {
+        self.options.graph_check = check_state;
+        self
+    }
+
+    /// Prints progress as the command line does
+    pub fn reporting_mode(mut self, mode: Mode) -> Builder {
+        self.events = Events::Mode(mode);
+        self
+    }
+
+    /// Sends progress to the given sink as the run goes along
+    pub fn event_sink(mut self, events: Arc<dyn EventSink>) -> Builder {
+        self.events = Events::Sink(events);
+        self
+    }
+
+    /// Connects to the target and resolves the units and their dependencies
+    pub fn resolve(self) -> Result<Plan, Error> {
+        match self.operation {
+            Operation::Apply | Operation::Rollback | Operation::Check | Operation::Test |
+            Operation::Plan | Operation::Drift => (),
+            other => return Err(Error::new(format!(
+                "{} can not be run through a plan", other.to_str()
+            )))
+        }
+
+        let mut targets = self.targets()?;
+        if targets.len() != 1 {
+            return Err(Error::new(
+                "A plan is made against a single target, several can only be run".to_string()
+            ))
+        }
+
+        let unit_specs = self.units.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
+        let engine = Engine::prepare(
+            &unit_specs,
+            self.operation,
+            &targets.remove(0),
+            &self.options,
+            &journal::run_id(),
+            self.events.sink(None)
+        )?;
+
+        Ok(Plan { engine })
+    }
+
+    /// Runs the operation against every target, returning a summary of each
+    pub fn run(self) -> Result<FleetSummary, Error> {
+        let targets = self.targets()?;
+        let unit_specs = self.units.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
+        let events = &self.events;
+
+        engine::run(&unit_specs, self.operation, &targets, self.options, &|host| events.sink(host))
+    }
+
+    /// The targets given by URL or selected from the inventory, or the local
+    /// machine if neither were
+    fn targets(&self) -> Result<Vec<TargetSpec>, Error> {
+        let adapter = self.adapter.as_deref();
+
+        let mut targets = match &self.inventory {
+            // Hosts without an adapter of their own use the one given
+            Some((path, selection)) => Inventory::load(path)?.select(selection)?.into_iter()
+                .map(|mut target| {
+                    if target.adapter.is_none() {
+                        target.adapter = adapter.map(|a| a.to_string());
+                    }
+                    target
+                })
+                .collect(),
+            None if self.target_urls.is_empty() => vec![TargetSpec::from_url(None, adapter)],
+            None => self.target_urls.iter()
+                .map(|url| TargetSpec::from_url(Some(url), adapter))
+                .collect::<Vec<TargetSpec>>()
+        };
+
+        for target in targets.iter_mut() {
+            target.default_args.merge_defaults(&self.default_args);
+        }
+
+        Ok(targets)
+    }
+}
+
+impl Events {
+    fn sink(&self, host: Option<String>) -> Arc<dyn EventSink> {
+        match self {
+            Events::Silent => Arc::new(Silent),
+            Events::Mode(mode) => ui::event_sink(*mode, host),
+            Events::Sink(sink) => sink.clone(),
+        }
+    }
+}
+
+/// A resolved graph of instances, ready to be run
+pub struct Plan {
+    engine: Engine,
+}
+
+impl Plan {
+    /// Every instance which the run will consider, dependencies first
+    pub fn instances(&self) -> Vec<InstanceReport> {
+        reports(self.engine.instances())
+    }
+
+    pub fn run(mut self) -> Result<Report, Error> {
+        self.engine.run()?;
+
+        Ok(Report {
+            instances: self.instances(),
+            summary: self.engine.summary(),
+        })
+    }
+}
+
+/// What happened to one instance of a unit
+#[derive(Debug, Clone)]
+pub struct InstanceReport {
+    pub id: InstanceId,
+    pub application_state: Option<ApplicationState>,
+    pub outcome: Option<Outcome>,
+    pub test_passed: Option<bool>,
+    /// Every execution of the unit for this instance, in the order they ran
+    pub executions: Vec<Execution>,
+}
+
+impl<'a> From<&'a Instance> for InstanceReport {
+    fn from(instance: &'a Instance) -> InstanceReport {
+        InstanceReport {
+            id: instance.id.clone(),
+            application_state: instance.application_state.clone(),
+            outcome: instance.outcome.clone(),
+            test_passed: instance.test_passed,
+            executions: instance.executions.clone(),
+        }
+    }
+}
+
+/// Reports on each of the instances as they stand
+pub(crate) fn reports(instances: &InstanceVec) -> Vec<InstanceReport> {
+    instances.iter().map(|rc| InstanceReport::from(&*rc.borrow())).collect()
+}
+
+pub struct Report {
+    /// Every instance, dependencies first
+    pub instances: Vec<InstanceReport>,
+    pub summary: Summary,
+}
+
+impl Report {
+    /// The status the command line would have exited with
+    pub fn exit_code(&self) -> i32 {
+        self.summary.exit_code()
+    }
+}
@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use colored::*;
+
+use crate::error::Error;
+use crate::execution::Target;
+use crate::meta::{self, Meta};
+use crate::resolver::load_unit;
+use crate::unit::{Instance, InstanceId, ArgSet};
+
+#[derive(Clone, Copy)]
+pub enum Format {
+    Terminal,
+    Markdown
+}
+
+/// Prints documentation for each of the named units
+pub fn document(
+    target: &mut Target,
+    unit_names: &[&str],
+    format: Format,
+    unit_paths: &[String]
+) -> Result<(), Error> {
+    for unit_name in unit_names.iter() {
+        let definition = Arc::new(load_unit(unit_name, unit_paths)?);
+        let instance_id = InstanceId::new(unit_name.to_string(), ArgSet::new());
+        let instance = Instance::new(definition, instance_id);
+        let meta = meta::load(target, &instance)?;
+
+        let rendered = match format {
+            Format::Terminal => render_terminal(unit_name, &meta),
+            Format::Markdown => render_markdown(unit_name, &meta),
+        };
+
+        println!("{}", rendered);
+    }
+
+    Ok(())
+}
+
+pub fn render_terminal(unit_name: &str, meta: &Meta) -> String {
+    let mut output = format!("{}\n", unit_name.bold());
+
+    if let Some(description) = &meta.description {
+        output.push_str(&format!("  {}\n", description.trim()));
+    }
+
+    if !meta.operations.is_empty() {
+        output.push_str(&format!("\n{}\n  {}\n", "OPERATIONS".bold(), meta.operations.join(", ")));
+    }
+
+    if !meta.args.is_empty() {
+        output.push_str(&format!("\n{}\n", "ARGUMENTS".bold()));
+
+        for arg in meta.args.iter() {
+            output.push_str(&format!("  {}", arg.name.green()));
+
+            if let Some(arg_type) = &arg.arg_type {
+                output.push_str(&format!(" <{}>", arg_type));
+            }
+
+            if let Some(default) = &arg.default {
+                output.push_str(&format!(" [default: {}]", default));
+            }
+
+            output.push('\n');
+
+            if let Some(help) = &arg.help {
+                output.push_str(&format!("      {}\n", help.trim()));
+            }
+        }
+    }
+
+    output
+}
+
+pub fn render_markdown(unit_name: &str, meta: &Meta) -> String {
+    let mut output = format!("# {}\n", unit_name);
+
+    if let Some(description) = &meta.description {
+        output.push_str(&format!("\n{}\n", description.trim()));
+    }
+
+    if !meta.operations.is_empty() {
+        output.push_str("\n## Operations\n\n");
+
+        for operation in meta.operations.iter() {
+            output.push_str(&format!("- `{}`\n", operation));
+        }
+    }
+
+    if !meta.args.is_empty() {
+        output.push_str("\n## Arguments\n\n");
+        output.push_str("| Name | Type | Default | Description |\n");
+        output.push_str("| ---- | ---- | ------- | ----------- |\n");
+
+        for arg in meta.args.iter() {
+            output.push_str(&format!("| `{}` | {} | {} | {} |\n",
+                arg.name,
+                arg.arg_type.as_deref().unwrap_or(""),
+                arg.default.as_ref().map(|d| format!("`{}`", d)).unwrap_or_default(),
+                arg.help.as_ref().map(|h| h.trim().replace("\n", " ")).unwrap_or_default()));
+        }
+    }
+
+    output
+}
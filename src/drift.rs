@@ -0,0 +1,65 @@
+use serde::Serialize;
+use toml::value::Table;
+
+use crate::error::Error;
+use crate::unit::{ApplicationState, Instance, Outcome};
+
+/// An instance whose check found it out of the state it declares, reported as
+/// a TOML document of `drift` tables:
+///
+/// ```toml
+/// [[drift]]
+/// host = "web1"
+/// unit = "nginx"
+/// state = "drifted"
+/// reason = "nginx.conf differs from template"
+///
+/// [drift.args]
+/// port = 80
+/// ```
+#[derive(Debug, Clone, Serialize)]
+pub struct Entry {
+    /// Only given when several targets are checked at once, in which case
+    /// the reporter for each target fills it in
+    pub host: Option<String>,
+    pub unit: String,
+    /// `drifted`, or `timed out` if the check didn't finish
+    pub state: String,
+    /// What the unit's check printed instead of `ok`
+    pub reason: String,
+    pub args: Table,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    drift: &'a [Entry],
+}
+
+impl Entry {
+    /// Describes the drift of a checked instance, if it has any
+    pub(crate) fn detect(instance: &Instance) -> Option<Entry> {
+        let (state, reason) = match (&instance.application_state, &instance.outcome) {
+            (Some(ApplicationState::NotApplied(reason)), _) =>
+                ("drifted", reason.trim_end().to_string()),
+            (None, Some(Outcome::TimedOut(timeout))) =>
+                ("timed out", format!("check timed out after {}s", timeout.as_secs())),
+            _ => return None
+        };
+
+        Some(Entry {
+            host: None,
+            unit: instance.id.name.clone(),
+            state: state.to_string(),
+            reason,
+            args: instance.id.args.vec.iter()
+                .map(|arg| (arg.name.clone(), arg.value.clone()))
+                .collect(),
+        })
+    }
+}
+
+pub fn render(entries: &[Entry]) -> Result<String, Error> {
+    toml::to_string(&Report { drift: entries }).map_err(|e|
+        wrap_error!("Could not render drift report: {}", e)
+    )
+}
@@ -1,117 +1,804 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use serde::Serialize;
+
 use crate::error::Error;
 use crate::execution::Target;
-use crate::ui::{Mode, report_execution};
-use crate::resolver::{resolve, InstanceVec};
+use crate::events::EventSink;
+use crate::resolver::{resolve, default_unit_paths, InstanceVec, InstanceRc};
 use crate::operation::Operation;
-use crate::unit::{ApplicationState};
+use crate::execution::Execution;
+use crate::unit::{ApplicationState, Outcome, Instance, DefinitionType, RetryPolicy};
+use crate::doc::{self, Format};
+use crate::inventory::TargetSpec;
+use crate::journal::{self, Entry};
+use crate::drift::Entry as DriftEntry;
+use crate::junit::{self, Suite};
+use crate::graph;
+use crate::events::Silent;
+use crate::api::{reports, InstanceReport};
+
+mod scheduler;
+
+use self::scheduler::{schedule, Node, Completion};
 
 pub type RunResult = Result<(), Error>;
 
-pub fn run(
-    unit_name: &str, 
-    operation_name: &str,
-    args_str: &str,
-    target_url: Option<&str>,
-    adapter: Option<&str>,
-    reporting_mode: Mode
-) -> RunResult {
-    let operation = Operation::from_str(operation_name)?;
-    let mut target = Target::try_new(target_url, adapter)?;
-
-    let ordered_unit_instances = resolve(&mut target, unit_name, args_str)?;
-
-    let mut engine = Engine {
-        ordered_instances: ordered_unit_instances,
-        target: &mut target,
-        operation: operation,
-        reporting_mode: reporting_mode
+/// Exit status used when a unit's apply or rollback failed
+pub const EXIT_FAILED: i32 = 1;
+
+/// Exit status used when drift found units whose check didn't report `ok`
+pub const EXIT_DRIFTED: i32 = 2;
+
+/// Exit status used when an apply or rollback ran but check afterwards showed
+/// the unit did not reach the intended state
+pub const EXIT_NOT_CONVERGED: i32 = 3;
+
+/// Instances affected by an apply or rollback, grouped by their outcome
+#[derive(Default, Serialize)]
+pub struct Summary {
+    pub converged: Vec<String>,
+    pub not_converged: Vec<String>,
+    pub failed: Vec<String>,
+    pub skipped: Vec<String>,
+    pub timed_out: Vec<String>,
+    pub drifted: Vec<String>,
+    pub reverted: Vec<String>,
+    pub tests_passed: Vec<String>,
+    pub tests_failed: Vec<String>,
+}
+
+impl Summary {
+    pub fn exit_code(&self) -> i32 {
+        if !self.failed.is_empty() || !self.timed_out.is_empty() || !self.tests_failed.is_empty() {
+            EXIT_FAILED
+        } else if !self.not_converged.is_empty() {
+            EXIT_NOT_CONVERGED
+        } else if !self.drifted.is_empty() {
+            EXIT_DRIFTED
+        } else {
+            0
+        }
+    }
+}
+
+/// Results of a run against each of its targets, in the order they were given
+pub struct FleetSummary {
+    pub hosts: Vec<HostSummary>,
+}
+
+pub struct HostSummary {
+    pub host: String,
+    /// Errors which stopped the run on this host, such as failing to connect
+    pub summary: Result<Summary, Error>,
+}
+
+impl FleetSummary {
+    pub fn exit_code(&self) -> i32 {
+        let codes = self.hosts.iter()
+            .map(|h| match &h.summary {
+                Ok(summary) => summary.exit_code(),
+                Err(_) => EXIT_FAILED
+            })
+            .collect::<Vec<i32>>();
+
+        // A failure on any host outranks hosts which merely didn't converge
+        match codes.contains(&EXIT_FAILED) {
+            true => EXIT_FAILED,
+            false => codes.into_iter().max().unwrap_or(0)
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Options {
+    /// Limits rollback to the requested unit, leaving its dependencies alone
+    pub root_only: bool,
+    /// Makes `plan` describe a rollback rather than an apply
+    pub plan_rollback: bool,
+    /// Stops executing any further units once one has failed
+    pub fail_fast: bool,
+    /// Rolls back everything applied during the run if any unit fails
+    pub atomic: bool,
+    /// Number of units which may execute on the target at once
+    pub jobs: usize,
+    /// How `doc` renders unit documentation
+    pub doc_format: Format,
+    /// How `graph` renders the instance graph
+    pub graph_format: graph::Format,
+    /// Makes `graph` check each unit, to colour nodes by the result
+    pub graph_check: bool,
+    /// Number of targets which may be operated upon at once
+    pub parallel_hosts: usize,
+    /// How long units may run for unless they give a timeout of their own
+    pub timeout: Option<Duration>,
+    /// Applies to units which don't give a retry policy of their own
+    pub retry: RetryPolicy,
+    /// Path of the journal kept on each target, if one is kept
+    pub journal: Option<String>,
+    /// Directories units are looked for in
+    pub unit_paths: Vec<String>,
+    /// Where to write a JUnit XML report of the run, if anywhere
+    pub report_file: Option<String>,
+}
+
+/// How long units may run for unless told otherwise
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1800);
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            root_only: false,
+            plan_rollback: false,
+            fail_fast: false,
+            atomic: false,
+            jobs: 1,
+            doc_format: Format::Terminal,
+            graph_format: graph::Format::Dot,
+            graph_check: false,
+            parallel_hosts: 1,
+            timeout: Some(DEFAULT_TIMEOUT),
+            retry: RetryPolicy::none(),
+            journal: None,
+            unit_paths: default_unit_paths(),
+            report_file: None,
+        }
+    }
+}
+
+/// Runs the operation against each of the given targets, reporting on each to
+/// the sink made for its host.  With a single target, errors are returned as
+/// they occur; with several, an error only ends the run on the host it
+/// occurred on.
+pub(crate) fn run(
+    root_specs: &[&str],
+    operation: Operation,
+    targets: &[TargetSpec],
+    options: Options,
+    event_sink: &(dyn Fn(Option<String>) -> Arc<dyn EventSink> + Sync)
+) -> Result<FleetSummary, Error> {
+    let run_id = journal::run_id();
+
+    let first_target = targets.first().ok_or_else(||
+        Error::new("No targets to operate upon".to_string())
+    )?;
+
+    if let Operation::Doc = operation {
+        doc::document(&mut first_target.connect()?, root_specs, options.doc_format, &options.unit_paths)?;
+        return Ok(FleetSummary { hosts: Vec::new() })
+    }
+
+    // The graph is printed in a format of its own, so nothing else is
+    if let Operation::Graph = operation {
+        let mut engine = Engine::prepare(root_specs, operation, first_target, &options, &run_id, Arc::new(Silent))?;
+        engine.run()?;
+
+        println!("{}", graph::render(engine.instances(), options.graph_format)?);
+        return Ok(FleetSummary { hosts: Vec::new() })
+    }
+
+    let suites = Mutex::new(Vec::new());
+
+    if targets.len() == 1 {
+        let reporter = event_sink(None);
+        let summary = run_target(root_specs, operation, first_target, &options, &run_id, reporter, &suites);
+
+        write_report(&options, suites, targets, operation)?;
+        let summary = summary?;
+
+        return Ok(FleetSummary {
+            hosts: vec![HostSummary { host: first_target.name.clone(), summary: Ok(summary) }]
+        })
+    }
+
+    let next_index = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::new());
+
+    thread::scope(|scope| {
+        for _ in 0..options.parallel_hosts.max(1).min(targets.len()) {
+            scope.spawn(|| loop {
+                let index = next_index.fetch_add(1, Ordering::SeqCst);
+                let target = match targets.get(index) {
+                    Some(target) => target,
+                    None => break
+                };
+
+                let host = target.name.clone();
+                let reporter = event_sink(Some(host.clone()));
+                let summary = run_target(
+                    root_specs, operation, target, &options, &run_id, reporter.clone(), &suites
+                );
+
+                if let Err(e) = &summary { reporter.error(e) }
+
+                results.lock().unwrap().push((index, HostSummary { host, summary }));
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(index, _)| *index);
+
+    let fleet = FleetSummary {
+        hosts: results.into_iter().map(|(_, host_summary)| host_summary).collect()
     };
 
-    engine.run()?;
+    write_report(&options, suites, targets, operation)?;
 
-    return Ok(())
+    Ok(fleet)
 }
 
-struct Engine <'a> {
-    reporting_mode: Mode,
+fn run_target(
+    root_specs: &[&str],
+    operation: Operation,
+    target_spec: &TargetSpec,
+    options: &Options,
+    run_id: &str,
+    events: Arc<dyn EventSink>,
+    suites: &Mutex<Vec<Suite>>
+) -> Result<Summary, Error> {
+    if let Operation::History = operation {
+        history(target_spec, root_specs, options, &*events)?;
+        return Ok(Summary::default())
+    }
+
+    let result = Engine::prepare(root_specs, operation, target_spec, options, run_id, events)
+        .and_then(|mut engine| engine.run().map(|_| engine));
+
+    if options.report_file.is_some() {
+        let suite = match &result {
+            Ok(engine) => Suite::record(&target_spec.name, engine.instances(), operation),
+            Err(e) => Suite::error(&target_spec.name, e)
+        };
+
+        suites.lock().unwrap().push(suite);
+    }
+
+    Ok(result?.summary())
+}
+
+/// Writes the JUnit report, if one was asked for, with targets in the order
+/// they were given
+fn write_report(
+    options: &Options,
+    suites: Mutex<Vec<Suite>>,
+    targets: &[TargetSpec],
+    operation: Operation
+) -> Result<(), Error> {
+    let path = match &options.report_file {
+        Some(path) => path,
+        None => return Ok(())
+    };
+
+    let mut suites = suites.into_inner().unwrap();
+    suites.sort_by_key(|suite| targets.iter().position(|t| t.name == suite.name()));
+
+    junit::write(path, &suites, operation)
+}
+
+impl Engine {
+    /// Connects to the target and resolves the graph of instances needed to
+    /// reach the root units' state, ready to run the operation upon
+    pub(crate) fn prepare(
+        root_specs: &[&str],
+        operation: Operation,
+        target_spec: &TargetSpec,
+        options: &Options,
+        run_id: &str,
+        events: Arc<dyn EventSink>
+    ) -> Result<Engine, Error> {
+        // Each job gets its own target so that, for SSH, concurrently
+        // executing units each have a connection, and a copy of the unit, to
+        // themselves.
+        let mut targets = Vec::new();
+        for _ in 0..options.jobs.max(1) {
+            let mut target = target_spec.connect()?;
+            target.set_default_timeout(options.timeout);
+            target.set_event_sink(events.clone());
+            targets.push(target);
+        }
+
+        let resolution = resolve(
+            &mut targets[0], root_specs, &target_spec.default_args, &options.unit_paths, &*events
+        )?;
+
+        Ok(Engine {
+            ordered_instances: resolution.ordered_instances,
+            roots: resolution.roots,
+            targets,
+            operation,
+            options: options.clone(),
+            events,
+            run_id: run_id.to_string()
+        })
+    }
+
+    /// The resolved instances, dependencies first
+    pub(crate) fn instances(&self) -> &InstanceVec {
+        &self.ordered_instances
+    }
+}
+
+/// Prints the journal entries kept on a target, limited to the named units if
+/// any are given
+fn history(
+    target_spec: &TargetSpec,
+    unit_names: &[&str],
+    options: &Options,
+    events: &dyn EventSink
+) -> Result<(), Error> {
+    let path = options.journal.as_ref().ok_or_else(||
+        Error::new("No journal to read history from, give its path with --journal".to_string())
+    )?;
+
+    let document = target_spec.connect()?.read_journal(path)?;
+    let entries = journal::parse(&document)?.into_iter()
+        .filter(|entry| unit_names.is_empty() || unit_names.contains(&entry.unit.as_str()))
+        .collect::<Vec<Entry>>();
+
+    events.history(&entries);
+
+    Ok(())
+}
+
+pub(crate) struct Engine {
+    options: Options,
+    events: Arc<dyn EventSink>,
+    /// Identifies this run in the journal
+    run_id: String,
     ordered_instances: InstanceVec,
-    target: &'a mut Target,
+    roots: InstanceVec,
+    targets: Vec<Target>,
     operation: Operation,
 }
 
-impl <'a> Engine <'a> {
+impl Engine {
+    /// Runs the operation, then sends the summary of every operation's run
     pub fn run(&mut self) -> RunResult {
         match self.operation {
             Operation::Apply => {
-                self.check(false)?;
-                self.apply()
+                self.check()?;
+                self.apply()?;
+                if self.options.atomic && self.summary().exit_code() != 0 {
+                    self.revert()?;
+                }
             },
             Operation::Rollback => {
-                self.check(false)?;
-                self.rollback()
+                self.check()?;
+                self.rollback()?;
             },
             Operation::Check => {
-                self.check(true)
+                self.check()?;
+            },
+            Operation::Test => {
+                self.check()?;
+                self.apply()?;
+                self.test()?;
+            },
+            Operation::Plan => {
+                self.check()?;
+                self.plan()?;
             },
-            other => Err(Error::new(format!(
+            Operation::Graph => {
+                if self.options.graph_check { self.check()? }
+            },
+            Operation::Drift => {
+                self.check()?;
+                self.drift()?;
+            },
+            other => return Err(Error::new(format!(
                 "{} is not a supported top-level operation", other.to_str()
             )))
         }
+
+        self.events.summary(&self.summary(), self.operation);
+        Ok(())
     }
 
     pub fn apply(&mut self) -> RunResult {
-        for instance in self.ordered_instances.iter()
-            .map(|rc| rc.borrow() )
-            .filter(|i|
-                match &i.application_state {
-                    Some(ApplicationState::NotApplied(_)) => true,
-                    _ => false
-                }
-            )
-        {
-            let run_result = self.target.execute(&instance, Operation::Apply)?;
+        let pending = self.apply_instances();
+        let order = self.ordered_instances.clone();
+        let fail_fast = self.options.fail_fast || self.options.atomic;
+
+        self.execute_pending(&order, &pending, Operation::Apply, fail_fast)
+    }
+
+    pub fn rollback(&mut self) -> RunResult {
+        let pending = self.rollback_instances();
+        let order: InstanceVec = self.ordered_instances.iter().rev().cloned().collect();
+
+        self.execute_pending(&order, &pending, Operation::Rollback, self.options.fail_fast)
+    }
 
-            report_execution(&run_result, self.reporting_mode, self.operation);
+    /// Rolls back every instance which this run's apply converged, in reverse,
+    /// so that the target ends up where it started.  This is best-effort: a
+    /// failed rollback only holds back the instances it depends upon.
+    fn revert(&mut self) -> RunResult {
+        let order: InstanceVec = self.ordered_instances.iter().rev().cloned().collect();
+        let pending: InstanceVec = order.iter()
+            .filter(|rc| matches!(rc.borrow().outcome, Some(Outcome::Converged)))
+            .cloned()
+            .collect();
+
+        self.execute_pending(&order, &pending, Operation::Rollback, false)?;
+
+        for instance in pending.iter() {
+            let mut instance_mut = instance.borrow_mut();
+
+            if let Some(Outcome::Converged) = instance_mut.outcome {
+                instance_mut.outcome = Some(Outcome::Reverted);
+            }
         }
 
         Ok(())
     }
 
-    pub fn rollback(&mut self) -> RunResult {
-        for instance in self.ordered_instances.iter()
-            .map(|rc| rc.borrow())
-            .filter(|i|
-                match i.application_state {
-                    Some(ApplicationState::Applied) => true,
-                    _ => false
+    /// Runs the `./test` executable of each applied instance which has one
+    pub fn test(&mut self) -> RunResult {
+        let order = self.ordered_instances.clone();
+        let nodes = graph_nodes(&order, Operation::Test, |instance|
+            instance.definition.has_test &&
+                matches!(instance.application_state, Some(ApplicationState::Applied))
+        );
+        let events = &*self.events;
+
+        schedule(&mut self.targets, &nodes, false,
+            |target, job| {
+                events.execution_started(&job.id, Operation::Test);
+                target.execute(job, Operation::Test)
+            },
+            |index, completion| {
+                if let Completion::Ran(run_result) = completion {
+                    events.execution_finished(&InstanceReport::from(&*order[index].borrow()), &run_result, None);
+
+                    let mut instance_mut = order[index].borrow_mut();
+                    instance_mut.test_passed = Some(run_result.success());
+                    instance_mut.executions.push(run_result);
                 }
-            )
-        {
-            let run_result = self.target.execute(&instance, Operation::Apply)?;
 
-            report_execution(&run_result, self.reporting_mode, self.operation);
+                true
+            }
+        )
+    }
+
+    pub fn plan(&mut self) -> RunResult {
+        let (planned_operation, instances) = if self.options.plan_rollback {
+            (Operation::Rollback, self.rollback_instances())
+        } else {
+            (Operation::Apply, self.apply_instances())
+        };
+
+        self.events.plan(&reports(&instances), planned_operation);
+
+        Ok(())
+    }
+
+    /// Instances which apply would execute, in the order it would execute them
+    fn apply_instances(&self) -> InstanceVec {
+        self.ordered_instances.iter()
+            .filter(|rc| matches!(rc.borrow().application_state, Some(ApplicationState::NotApplied(_))))
+            .cloned()
+            .collect()
+    }
+
+    /// Instances which rollback would execute, in the order it would execute them
+    fn rollback_instances(&self) -> InstanceVec {
+        // Instances are ordered with dependencies first and the requested root
+        // units last, so walking them backwards tears dependents down before
+        // the units they depend upon.
+        let candidates: InstanceVec = if self.options.root_only {
+            self.ordered_instances.iter()
+                .filter(|rc| self.roots.iter().any(|root| Rc::ptr_eq(root, rc)))
+                .cloned()
+                .collect()
+        } else {
+            self.ordered_instances.clone()
+        };
+
+        candidates.iter().rev()
+            .filter(|rc| matches!(rc.borrow().application_state, Some(ApplicationState::Applied)))
+            .cloned()
+            .collect()
+    }
+
+    /// Walks the full instance graph in the given order, executing the
+    /// operation on those instances which are pending as soon as the
+    /// instances they wait upon have finished.
+    ///
+    /// When an instance fails, any instance whose state hinges upon it is
+    /// skipped: its dependents when applying, its dependencies when rolling
+    /// back.  Instances in independent branches of the graph still execute,
+    /// unless `fail_fast` is set in which case everything not yet started is
+    /// skipped.
+    fn execute_pending(
+        &mut self,
+        order: &InstanceVec,
+        pending: &InstanceVec,
+        operation: Operation,
+        fail_fast: bool
+    ) -> RunResult {
+        let pending_signatures = pending.iter()
+            .map(|rc| rc.borrow().id.signature())
+            .collect::<HashSet<String>>();
+        let mut nodes = graph_nodes(order, operation, |instance|
+            pending_signatures.contains(&instance.id.signature())
+        );
+        let events = &*self.events;
+        let default_retry = &self.options.retry;
+        let no_retry = RetryPolicy::none();
+
+        // The state of an instance whose check timed out is unknown, so
+        // whatever hinges upon it is skipped
+        for (node, rc) in nodes.iter_mut().zip(order.iter()) {
+            let instance = rc.borrow();
+
+            node.failed = match instance.outcome {
+                Some(Outcome::TimedOut(_)) => instance.application_state.is_none(),
+                _ => false
+            };
         }
 
+        schedule(&mut self.targets, &nodes, fail_fast,
+            |target, job| {
+                let retry = match operation {
+                    Operation::Apply => job.definition.retry.as_ref().unwrap_or(default_retry),
+                    _ => &no_retry
+                };
+                let mut attempt = 1;
+                let mut earlier_attempts = Vec::new();
+
+                events.execution_started(&job.id, operation);
+
+                loop {
+                    let execution = target.execute(job, operation)?;
+                    let check = match execution.success() {
+                        true => Some(target.execute(job, Operation::Check)?),
+                        false => None
+                    };
+
+                    if attempt > retry.count || !retryable(retry, &execution, check.as_ref()) {
+                        return Ok((earlier_attempts, execution, check))
+                    }
+
+                    let delay = retry.delay(attempt);
+                    events.execution_retrying(&job.id, &execution, attempt, retry.count + 1, delay);
+                    earlier_attempts.push(execution);
+                    earlier_attempts.extend(check);
+                    thread::sleep(delay);
+                    attempt += 1;
+                }
+            },
+            |index, completion| {
+                let instance = &order[index];
+                let outcome = match completion {
+                    Completion::Skipped => {
+                        events.execution_skipped(&InstanceReport::from(&*instance.borrow()), operation);
+                        Outcome::Skipped
+                    },
+                    Completion::Ran((earlier_attempts, execution, check)) => {
+                        {
+                            let mut instance_mut = instance.borrow_mut();
+                            instance_mut.executions.extend(earlier_attempts);
+                            instance_mut.executions.push(execution.clone());
+                            instance_mut.executions.extend(check.clone());
+                        }
+
+                        let outcome = match (check, execution.timed_out) {
+                            (Some(check), _) => match check.timed_out {
+                                Some(timeout) => Outcome::TimedOut(timeout),
+                                None => {
+                                    record_check(instance, &check);
+                                    verify(&instance.borrow(), operation)
+                                }
+                            },
+                            (None, Some(timeout)) => Outcome::TimedOut(timeout),
+                            (None, None) => Outcome::Failed(execution.exit_code)
+                        };
+
+                        events.execution_finished(&InstanceReport::from(&*instance.borrow()), &execution, Some(&outcome));
+                        outcome
+                    }
+                };
+
+                let converged = matches!(outcome, Outcome::Converged);
+
+                instance.borrow_mut().outcome = Some(outcome);
+                converged
+            }
+        )?;
+
+        self.journal(pending, operation);
+
         Ok(())
     }
 
-    pub fn check(&mut self, report: bool) -> RunResult {
-        for instance in self.ordered_instances.iter() {
-            let run_result = self.target.execute(&instance.borrow(), Operation::Check)?;
-            let output_str = run_result.stdout.trim_end();
+    /// Records the outcome of each executed instance in the target's journal.
+    /// Failing to do so doesn't undo what was done, so it only warrants a
+    /// warning.
+    fn journal(&mut self, executed: &InstanceVec, operation: Operation) {
+        let path = match &self.options.journal {
+            Some(path) => path.clone(),
+            None => return
+        };
 
-            let mut instance_mut = instance.borrow_mut();
+        let mut entries = Vec::new();
 
-            if output_str == "ok" {
-                instance_mut.application_state = Some(ApplicationState::Applied);
-            } else {
-                instance_mut.application_state =
-                    Some(ApplicationState::NotApplied(output_str.to_string()));
+        for instance in executed.iter() {
+            match Entry::record(&instance.borrow(), operation, &self.run_id) {
+                Ok(Some(entry)) => entries.push(entry),
+                Ok(None) => (),
+                Err(e) => return self.events.warning(&e.msg)
             }
+        }
+
+        if entries.is_empty() { return }
 
-            if report { report_execution(&run_result, self.reporting_mode, self.operation) }
+        let result = journal::render(entries)
+            .and_then(|rendered| self.targets[0].append_journal(&path, &rendered));
+
+        if let Err(e) = result {
+            self.events.warning(&format!("Could not update journal: {}", e.msg))
         }
+    }
+
+    pub fn check(&mut self) -> RunResult {
+        let order = self.ordered_instances.clone();
+        let nodes = graph_nodes(&order, Operation::Check, |_| true);
+        let events = &*self.events;
+        let operation = self.operation;
+
+        schedule(&mut self.targets, &nodes, false,
+            |target, job| target.execute(job, Operation::Check),
+            |index, completion| {
+                if let Completion::Ran(run_result) = completion {
+                    order[index].borrow_mut().executions.push(run_result.clone());
+
+                    match run_result.timed_out {
+                        Some(timeout) => order[index].borrow_mut().outcome = Some(Outcome::TimedOut(timeout)),
+                        None => record_check(&order[index], &run_result)
+                    }
+
+                    events.check_finished(&InstanceReport::from(&*order[index].borrow()), &run_result, operation);
+                }
+
+                true
+            }
+        )
+    }
+
+    /// Reports every instance whose check found it out of its declared state
+    pub fn drift(&mut self) -> RunResult {
+        let entries = self.ordered_instances.iter()
+            .filter_map(|rc| DriftEntry::detect(&rc.borrow()))
+            .collect::<Vec<DriftEntry>>();
+
+        self.events.drift(&entries);
 
         Ok(())
     }
+
+    pub(crate) fn summary(&self) -> Summary {
+        let mut summary = Summary::default();
+
+        for instance in self.ordered_instances.iter().map(|rc| rc.borrow()) {
+            let label = instance.id.to_string();
+
+            if let (Operation::Drift, Some(ApplicationState::NotApplied(_))) =
+                (self.operation, &instance.application_state)
+            {
+                summary.drifted.push(label.clone());
+            }
+
+            match instance.outcome {
+                Some(Outcome::Converged) => summary.converged.push(label.clone()),
+                Some(Outcome::NotConverged(_)) => summary.not_converged.push(label.clone()),
+                Some(Outcome::Failed(_)) => summary.failed.push(label.clone()),
+                Some(Outcome::Skipped) => summary.skipped.push(label.clone()),
+                Some(Outcome::TimedOut(_)) => summary.timed_out.push(label.clone()),
+                Some(Outcome::Reverted) => summary.reverted.push(label.clone()),
+                None => ()
+            }
+
+            match instance.test_passed {
+                Some(true) => summary.tests_passed.push(label),
+                Some(false) => summary.tests_failed.push(label),
+                None => ()
+            }
+        }
+
+        summary
+    }
+}
+
+/// Builds the graph of instances for the scheduler.  Apply and check wait upon
+/// an instance's dependencies, whereas rollback waits upon its dependents.
+fn graph_nodes<F>(order: &InstanceVec, operation: Operation, runnable: F) -> Vec<Node>
+    where F: Fn(&Instance) -> bool
+{
+    let indices = order.iter().enumerate()
+        .map(|(index, rc)| (rc.borrow().id.signature(), index))
+        .collect::<HashMap<String, usize>>();
+
+    let mut nodes = order.iter()
+        .map(|rc| {
+            let instance = rc.borrow();
+
+            // Manifests only group their dependencies, so they have nothing
+            // to check or apply on the target.
+            let is_manifest = matches!(instance.definition.definition_type, DefinitionType::Manifest);
+
+            Node {
+                job: instance.job(),
+                prerequisites: Vec::new(),
+                runnable: !is_manifest && runnable(&instance),
+                failed: false
+            }
+        })
+        .collect::<Vec<Node>>();
+
+    for (index, rc) in order.iter().enumerate() {
+        for dependency in rc.borrow().dependencies.iter() {
+            let dependency_index = match indices.get(dependency) {
+                Some(i) => *i,
+                None => continue
+            };
+
+            match operation {
+                Operation::Rollback => nodes[dependency_index].prerequisites.push(index),
+                _ => nodes[index].prerequisites.push(dependency_index)
+            }
+        }
+    }
+
+    nodes
+}
+
+/// Whether an attempt at executing an instance failed in a way which the retry
+/// policy says may go away if it's attempted again.  A policy limited to
+/// certain exit codes retries nothing else, timeouts and failures to converge
+/// included.
+fn retryable(policy: &RetryPolicy, execution: &Execution, check: Option<&Execution>) -> bool {
+    if !policy.exit_codes.is_empty() {
+        return execution.timed_out.is_none() && policy.exit_codes.contains(&execution.exit_code)
+    }
+
+    if !execution.success() {
+        return true
+    }
+
+    match check {
+        Some(check) => check.timed_out.is_some() || check.stdout.trim_end() != "ok",
+        None => false
+    }
+}
+
+/// Records the application state reported by an instance's check
+fn record_check(instance: &InstanceRc, run_result: &Execution) {
+    let output_str = run_result.stdout.trim_end();
+
+    let mut instance_mut = instance.borrow_mut();
+
+    if output_str == "ok" {
+        instance_mut.application_state = Some(ApplicationState::Applied);
+    } else {
+        instance_mut.application_state =
+            Some(ApplicationState::NotApplied(output_str.to_string()));
+    }
+}
+
+/// Determines from the check re-run after an apply or rollback whether the
+/// instance actually reached the state the operation was meant to leave it in.
+fn verify(instance: &Instance, operation: Operation) -> Outcome {
+    match (operation, &instance.application_state) {
+        (Operation::Apply, Some(ApplicationState::NotApplied(reason))) =>
+            Outcome::NotConverged(reason.to_string()),
+        (Operation::Rollback, Some(ApplicationState::Applied)) =>
+            Outcome::NotConverged("check still reports ok".to_string()),
+        _ => Outcome::Converged,
+    }
 }
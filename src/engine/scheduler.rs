@@ -0,0 +1,147 @@
+use std::sync::mpsc;
+use std::thread;
+
+use crate::error::Error;
+use crate::execution::Target;
+use crate::unit::Job;
+
+/// A unit of work in the graph handed to the scheduler
+pub struct Node {
+    /// What workers need to execute the node's instance
+    pub job: Job,
+    /// Indices of the nodes which must finish before this one may start
+    pub prerequisites: Vec<usize>,
+    /// Whether this node has work to do.  Nodes which don't are still part of
+    /// the graph so that failures propagate through them.
+    pub runnable: bool,
+    /// Whether this node already failed earlier in the run, in which case it
+    /// isn't run and the nodes waiting upon it are skipped
+    pub failed: bool,
+}
+
+pub enum Completion<T> {
+    Ran(T),
+    /// A prerequisite failed or the run was aborted, so the node never ran
+    Skipped,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Waiting,
+    Running,
+    Finished { broken: bool },
+}
+
+/// Runs `work` for every runnable node as soon as all of its prerequisites have
+/// finished, executing as many nodes at once as there are targets.
+///
+/// `done` is called on this thread with each node's result and returns whether
+/// the node succeeded.  Nodes with a failed prerequisite are skipped, as is
+/// everything not yet started once a node fails if `fail_fast` is set.
+/// Errors from `work` stop any further nodes from starting and are returned
+/// once running nodes finish.
+pub fn schedule<T, W, D>(
+    targets: &mut [Target],
+    nodes: &[Node],
+    fail_fast: bool,
+    work: W,
+    mut done: D
+) -> Result<(), Error>
+    where T: Send,
+          W: Fn(&mut Target, &Job) -> Result<T, Error> + Sync,
+          D: FnMut(usize, Completion<T>) -> bool
+{
+    thread::scope(|scope| {
+        let (result_sender, result_receiver) = mpsc::channel();
+        let mut job_senders = Vec::new();
+
+        for (worker, target) in targets.iter_mut().enumerate() {
+            let (job_sender, job_receiver) = mpsc::channel::<usize>();
+            let result_sender = result_sender.clone();
+            let work = &work;
+
+            scope.spawn(move || {
+                for index in job_receiver {
+                    let result = work(target, &nodes[index].job);
+                    if result_sender.send((worker, index, result)).is_err() { break }
+                }
+            });
+
+            job_senders.push(job_sender);
+        }
+
+        let mut states = vec![State::Waiting; nodes.len()];
+        let mut idle_workers: Vec<usize> = (0..job_senders.len()).rev().collect();
+        let mut running = 0;
+        let mut aborted = false;
+        let mut error: Option<Error> = None;
+
+        loop {
+            let mut progressed = error.is_none();
+
+            while progressed {
+                progressed = false;
+
+                for index in 0..nodes.len() {
+                    if states[index] != State::Waiting { continue }
+
+                    let node = &nodes[index];
+                    let mut ready = true;
+                    let mut blocked = aborted;
+
+                    for prerequisite in node.prerequisites.iter() {
+                        match states[*prerequisite] {
+                            State::Finished { broken } => blocked = blocked || broken,
+                            _ => ready = false
+                        }
+                    }
+
+                    if !ready { continue }
+
+                    if blocked {
+                        if node.runnable { done(index, Completion::Skipped); }
+                        states[index] = State::Finished { broken: true };
+                        progressed = true;
+                    } else if node.failed {
+                        states[index] = State::Finished { broken: true };
+                        progressed = true;
+                    } else if !node.runnable {
+                        states[index] = State::Finished { broken: false };
+                        progressed = true;
+                    } else if let Some(worker) = idle_workers.pop() {
+                        job_senders[worker].send(index).map_err(|_|
+                            Error::new(format!("Worker {} exited unexpectedly", worker))
+                        )?;
+                        states[index] = State::Running;
+                        running += 1;
+                    }
+                }
+            }
+
+            if running == 0 { break }
+
+            let (worker, index, result) = result_receiver.recv().map_err(|_|
+                Error::new("All workers exited unexpectedly".to_string())
+            )?;
+
+            running -= 1;
+            idle_workers.push(worker);
+
+            let succeeded = match result {
+                Ok(value) => done(index, Completion::Ran(value)),
+                Err(e) => {
+                    error = error.or(Some(e));
+                    false
+                }
+            };
+
+            states[index] = State::Finished { broken: !succeeded };
+            aborted = aborted || (!succeeded && fail_fast);
+        }
+
+        match error {
+            Some(e) => Err(e),
+            None => Ok(())
+        }
+    })
+}
@@ -5,10 +5,9 @@ use std::fmt;
 pub type BoxedResult<T> = Result<T, Box<dyn std::error::Error>>;
 
 #[macro_export]
-
 macro_rules! wrap_error {
     ($format_string: literal, $error: expr) => {
-        Error::new(format!($format_string, $error.to_string()));
+        Error::new(format!($format_string, $error.to_string()))
     }
 }
 
@@ -19,7 +18,7 @@ pub struct Error {
 
 impl Error {
     pub fn new(msg: String) -> Error {
-        Error{msg: msg}
+        Error{msg}
     }
 }
 
@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use crate::drift::Entry as DriftEntry;
+use crate::engine::Summary;
+use crate::error::Error;
+use crate::execution::{Execution, OutputLine};
+use crate::journal::Entry as JournalEntry;
+use crate::operation::Operation;
+use crate::api::InstanceReport;
+use crate::unit::{InstanceId, Outcome};
+
+/// Receives progress from the resolver and engine as a run goes along.  Every
+/// event does nothing by default, so sinks need only handle those they're
+/// interested in.
+///
+/// Events about executions may be sent from the threads executing them when
+/// running with several jobs, hence `Send + Sync`.  Those events only carry the
+/// instance's id, as its state stays with the thread which owns the graph.
+pub trait EventSink: Send + Sync {
+    /// The root units are about to be resolved into a graph
+    fn resolution_started(&self, _root_specs: &[&str]) {}
+
+    /// An instance's dependencies were found, either by executing its `deps`
+    /// or reading its manifest
+    fn deps_discovered(&self, _id: &InstanceId, _dependencies: &[InstanceId]) {}
+
+    /// Resolution finished with these instances, dependencies first
+    fn resolution_finished(&self, _instances: &[InstanceReport]) {}
+
+    /// An instance's check finished during a run of `operation`
+    fn check_finished(&self, _instance: &InstanceReport, _execution: &Execution, _operation: Operation) {}
+
+    /// An apply, rollback or test of an instance is about to start
+    fn execution_started(&self, _id: &InstanceId, _operation: Operation) {}
+
+    /// A unit printed a line, on either of its streams, while executing
+    fn output_line(&self, _id: &InstanceId, _operation: Operation, _line: &OutputLine) {}
+
+    /// An apply, rollback or test of an instance finished.  For applies and
+    /// rollbacks, `outcome` says whether the check run afterwards found that
+    /// it converged.
+    fn execution_finished(&self, _instance: &InstanceReport, _execution: &Execution, _outcome: Option<&Outcome>) {}
+
+    /// An attempt at an execution failed and will be retried after `delay`
+    fn execution_retrying(
+        &self,
+        _id: &InstanceId,
+        _execution: &Execution,
+        _attempt: u32,
+        _attempts: u32,
+        _delay: Duration
+    ) {}
+
+    /// An instance wasn't executed because of an earlier failure
+    fn execution_skipped(&self, _instance: &InstanceReport, _operation: Operation) {}
+
+    /// The instances which `operation` would execute, in order
+    fn plan(&self, _instances: &[InstanceReport], _operation: Operation) {}
+
+    /// Instances whose check found them out of their declared state
+    fn drift(&self, _entries: &[DriftEntry]) {}
+
+    /// Entries read from the target's journal
+    fn history(&self, _entries: &[JournalEntry]) {}
+
+    /// The run finished
+    fn summary(&self, _summary: &Summary, _operation: Operation) {}
+
+    /// Something went wrong which doesn't stop the run
+    fn warning(&self, _message: &str) {}
+
+    /// Something went wrong which stopped the run
+    fn error(&self, _error: &Error) {}
+}
+
+/// Ignores every event
+pub struct Silent;
+
+impl EventSink for Silent {}
@@ -1,23 +1,36 @@
-use crate::unit::{Instance};
+use std::time::Duration;
+
+use crate::unit::Job;
 use crate::error::Error;
 use crate::operation::Operation;
 
 mod target;
 mod adapter;
+mod args_fd;
+mod process;
+mod output;
 
 pub use self::target::Target;
+pub use self::output::{OutputLine, Stream};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Execution {
     pub unit_name: String,
+    pub operation: Operation,
     pub exit_code: i32,
     pub stdout: String,
     pub stderr: String,
+    /// Lines of stdout and stderr, in the order they arrived
+    pub output: Vec<OutputLine>,
+    /// The limit the execution was stopped for exceeding, if it was
+    pub timed_out: Option<Duration>,
+    /// How long the unit ran for
+    pub duration: Duration,
 }
 
 impl Execution {
     pub fn success(&self) -> bool {
-        self.exit_code == 0
+        self.exit_code == 0 && self.timed_out.is_none()
     }
 }
 
@@ -25,5 +38,21 @@ pub type ExecutionResult = Result<Execution, Error>;
 
 pub trait Executor {
     fn init(&mut self) -> Result<(), Error>;
-    fn execute(&mut self, unit: &Instance, operation: Operation) -> ExecutionResult;
+    /// Executes an operation of the unit, stopping it after `timeout` if given.
+    /// Each line of output is handed to `on_line` as soon as it's read.
+    fn execute(
+        &mut self,
+        unit: &Job,
+        operation: Operation,
+        timeout: Option<Duration>,
+        on_line: &mut dyn FnMut(&OutputLine)
+    ) -> ExecutionResult;
+
+    /// Appends rendered entries to the journal at `path` on the target,
+    /// creating it if need be
+    fn append_journal(&mut self, path: &str, entries: &str) -> Result<(), Error>;
+
+    /// Reads the journal at `path` on the target, which is empty if it
+    /// doesn't exist yet
+    fn read_journal(&mut self, path: &str) -> Result<String, Error>;
 }
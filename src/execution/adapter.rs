@@ -1,15 +1,18 @@
 use std::path::Path;
 use std::fs;
-use std::io::Read;
+use std::io::Write;
+use std::os::unix::process::CommandExt;
 use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
 
-use crate::unit::{Instance};
+use crate::unit::Job;
 use crate::error::Error;
 use crate::fs_util;
 
-use super::{Executor, Operation, Execution, ExecutionResult};
+use super::{Executor, Operation, Execution, ExecutionResult, OutputLine};
+use super::{args_fd, process};
 
-const DEFAULT_DIRS: &'static [&'static str] = &["/usr/lib/sysunit/adapters"];
+const DEFAULT_DIRS: &[&str] = &["/usr/lib/sysunit/adapters"];
 
 pub struct Adapter {
     path: String,
@@ -26,18 +29,29 @@ impl Executor for Adapter {
         Ok(())
     }
 
-    fn execute(&mut self, unit: &Instance, operation: Operation) -> ExecutionResult {
-        let definition = unit.definition_rc.clone();
+    fn execute(
+        &mut self,
+        unit: &Job,
+        operation: Operation,
+        timeout: Option<Duration>,
+        on_line: &mut dyn FnMut(&OutputLine)
+    ) -> ExecutionResult {
+        let definition = unit.definition.clone();
         let unit_path = &definition.path;
 
         let mut command = Command::new(&self.path);
         command
             .arg(operation.to_str())
             .arg(unit_path)
+            .process_group(0)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
 
-        let mut child = match command.spawn() {
+        // Adapters pass the descriptor on to the unit they run
+        let args_pipe = args_fd::attach(&mut command, &unit.id.args)?;
+
+        let started = Instant::now();
+        let child = match args_pipe.spawn(&mut command) {
             Ok(c) => c,
             Err(e) => {
                 let msg = format!("Could not execute unit adapter `{}`, path: {}, error: {:?}",
@@ -50,40 +64,77 @@ impl Executor for Adapter {
             }
         };
 
-        let status = match child.wait() {
-            Ok(s) => s,
-            Err(_) => {
-                let error = Error::new(format!("[{}] killed by external signal", definition.name));
-                return Err(error)
-            }
-        };
-
-        let mut stdout = String::new();
-        let mut stderr = String::new();
-
-        child
-            .stdout
-            .unwrap()
-            .read_to_string(&mut stdout)
-            .unwrap();
-
-        child
-            .stderr
-            .unwrap()
-            .read_to_string(&mut stderr)
-            .unwrap();
+        let finished = process::wait(child, timeout, on_line).map_err(|_|
+            Error::new(format!("[{}] killed by external signal", definition.name))
+        )?;
 
-        let exit_code = status.code().unwrap();
+        let exit_code = match (finished.status.code(), finished.timed_out) {
+            (Some(c), _) => c,
+            (None, true) => -1,
+            (None, false) => return Err(Error::new(format!(
+                "[{}] killed by external signal", definition.name
+            )))
+        };
 
         let execution = Execution {
             unit_name: definition.name.clone(),
-            stdout: stdout,
-            stderr: stderr,
-            exit_code: exit_code
+            operation,
+            stdout: String::from_utf8_lossy(&finished.output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&finished.output.stderr).to_string(),
+            output: finished.output.lines,
+            exit_code,
+            timed_out: match finished.timed_out {
+                true => timeout,
+                false => None
+            },
+            duration: started.elapsed()
         };
 
         Ok(execution)
     }
+
+    fn append_journal(&mut self, path: &str, entries: &str) -> Result<(), Error> {
+        self.journal_command("journal-append", path, entries)?;
+
+        Ok(())
+    }
+
+    fn read_journal(&mut self, path: &str) -> Result<String, Error> {
+        self.journal_command("journal-read", path, "")
+    }
+}
+
+impl Adapter {
+    /// Journals are kept by running `adapter journal-append <path>` with the
+    /// entries on stdin, and read by `adapter journal-read <path>` printing
+    /// the journal's contents
+    fn journal_command(&self, command_name: &str, path: &str, input: &str) -> Result<String, Error> {
+        let journal_error = |e: std::io::Error|
+            Error::new(format!("Adapter {} failed on {}: {}", command_name, path, e));
+
+        let mut child = args_fd::spawn(Command::new(&self.path)
+            .arg(command_name)
+            .arg(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+        ).map_err(journal_error)?;
+
+        // Dropping stdin once written gives the adapter its EOF
+        child.stdin.take().unwrap().write_all(input.as_bytes()).map_err(journal_error)?;
+
+        let output = child.wait_with_output().map_err(journal_error)?;
+
+        if !output.status.success() {
+            return Err(Error::new(format!("Adapter {} failed on {}: {}",
+                command_name,
+                path,
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            )))
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
 }
 
 fn load_executable(adapter_name: &str) -> Result<String, Error> {
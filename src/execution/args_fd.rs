@@ -0,0 +1,119 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command};
+use std::sync::RwLock;
+
+use crate::error::Error;
+use crate::unit::ArgSet;
+
+/// File descriptor on which units may read a TOML document of their arguments
+pub const ARGS_FD: i32 = 3;
+
+/// Environment variable telling units which descriptor carries their arguments
+pub const ARGS_FD_VAR: &str = "SYSUNIT_ARGS_FD";
+
+/// Held for writing while a new pipe's descriptors aren't yet close-on-exec,
+/// and for reading while spawning, so that processes spawned by other jobs
+/// meanwhile don't inherit them
+static SPAWN_LOCK: RwLock<()> = RwLock::new(());
+
+/// The parent's copy of the read end of the pipe carrying a unit's arguments
+/// document, which is closed once the unit has been spawned
+pub struct ArgsPipe {
+    _reader: File,
+}
+
+/// Arranges for the process spawned by `command` to find a TOML document of
+/// the given arguments on `ARGS_FD`, which must then be spawned with
+/// `ArgsPipe::spawn`.
+///
+/// The document is written to the pipe up front, so that nothing is left to
+/// do once the unit is running and units which never read it can't hold us
+/// up.  It must fit in the pipe's buffer for that.
+pub fn attach(command: &mut Command, args: &ArgSet) -> Result<ArgsPipe, Error> {
+    let document = args.to_toml()?;
+    let pipe_error = |e: io::Error| wrap_error!("Could not create arguments pipe: {}", e);
+
+    let (reader, mut writer) = pipe().map_err(pipe_error)?;
+
+    writer.write_all(document.as_bytes()).map_err(|e| match e.kind() {
+        io::ErrorKind::WouldBlock => Error::new(format!(
+            "Arguments document of {} bytes is too large to pass to units", document.len()
+        )),
+        _ => pipe_error(e)
+    })?;
+
+    // Closing our end is what gives the unit its EOF
+    drop(writer);
+
+    let read_fd = reader.as_raw_fd();
+
+    command.env(ARGS_FD_VAR, ARGS_FD.to_string());
+
+    unsafe {
+        command.pre_exec(move || {
+            // dup2 leaves the new descriptor open across exec, but is a no-op
+            // when the pipe already landed on ARGS_FD
+            let result = match read_fd == ARGS_FD {
+                true => libc::fcntl(ARGS_FD, libc::F_SETFD, 0),
+                false => libc::dup2(read_fd, ARGS_FD)
+            };
+
+            match result {
+                -1 => Err(io::Error::last_os_error()),
+                _ => Ok(())
+            }
+        });
+    }
+
+    Ok(ArgsPipe { _reader: reader })
+}
+
+impl ArgsPipe {
+    /// Spawns the command the pipe was attached to, then closes our copy of
+    /// the read end
+    pub fn spawn(self, command: &mut Command) -> io::Result<Child> {
+        spawn(command)
+    }
+}
+
+/// Spawns a command without letting it inherit a pipe which is being created
+/// at the same time for another unit
+pub fn spawn(command: &mut Command) -> io::Result<Child> {
+    let _guard = SPAWN_LOCK.read().unwrap_or_else(|e| e.into_inner());
+
+    command.spawn()
+}
+
+/// Creates a pipe whose ends are both close-on-exec, the write end also being
+/// non-blocking
+fn pipe() -> io::Result<(File, File)> {
+    let _guard = SPAWN_LOCK.write().unwrap_or_else(|e| e.into_inner());
+    let mut fds = [0; 2];
+
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error())
+    }
+
+    let pipe = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+
+    for fd in fds.iter() {
+        set_flags(*fd, libc::F_GETFD, libc::F_SETFD, libc::FD_CLOEXEC)?;
+    }
+
+    set_flags(fds[1], libc::F_GETFL, libc::F_SETFL, libc::O_NONBLOCK)?;
+
+    Ok(pipe)
+}
+
+fn set_flags(fd: i32, get: i32, set: i32, flags: i32) -> io::Result<()> {
+    let current = unsafe { libc::fcntl(fd, get) };
+
+    if current == -1 || unsafe { libc::fcntl(fd, set, current | flags) } == -1 {
+        return Err(io::Error::last_os_error())
+    }
+
+    Ok(())
+}
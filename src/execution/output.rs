@@ -0,0 +1,87 @@
+use std::time::SystemTime;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stream {
+    Stdout,
+    Stderr
+}
+
+impl Stream {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            Stream::Stdout => "stdout",
+            Stream::Stderr => "stderr",
+        }
+    }
+}
+
+/// A line of a unit's output, timestamped as it arrived
+#[derive(Debug, Clone)]
+pub struct OutputLine {
+    pub stream: Stream,
+    pub text: String,
+    pub timestamp: SystemTime,
+}
+
+/// Everything a unit printed, in full and as lines in the order they arrived
+pub struct Collected {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub lines: Vec<OutputLine>,
+}
+
+/// Gathers output from both of a unit's streams as it's read, handing each
+/// line on as soon as it's complete
+pub struct Collector<'a> {
+    on_line: &'a mut dyn FnMut(&OutputLine),
+    collected: Collected,
+    /// Partial lines of stdout and stderr, awaiting their newline
+    pending: [Vec<u8>; 2],
+}
+
+impl<'a> Collector<'a> {
+    pub fn new(on_line: &'a mut dyn FnMut(&OutputLine)) -> Collector<'a> {
+        Collector {
+            on_line,
+            collected: Collected { stdout: Vec::new(), stderr: Vec::new(), lines: Vec::new() },
+            pending: [Vec::new(), Vec::new()],
+        }
+    }
+
+    pub fn push(&mut self, stream: Stream, bytes: &[u8]) {
+        match stream {
+            Stream::Stdout => self.collected.stdout.extend_from_slice(bytes),
+            Stream::Stderr => self.collected.stderr.extend_from_slice(bytes),
+        }
+
+        let index = stream as usize;
+        self.pending[index].extend_from_slice(bytes);
+
+        while let Some(end) = self.pending[index].iter().position(|b| *b == b'\n') {
+            let line = self.pending[index].drain(..=end).collect::<Vec<u8>>();
+            self.emit(stream, &line[..end]);
+        }
+    }
+
+    /// Hands on any last lines which weren't terminated by a newline
+    pub fn finish(mut self) -> Collected {
+        for stream in [Stream::Stdout, Stream::Stderr].iter() {
+            let line = std::mem::take(&mut self.pending[*stream as usize]);
+
+            if !line.is_empty() { self.emit(*stream, &line) }
+        }
+
+        self.collected
+    }
+
+    fn emit(&mut self, stream: Stream, line: &[u8]) {
+        let line = OutputLine {
+            stream,
+            text: String::from_utf8_lossy(line).to_string(),
+            timestamp: SystemTime::now(),
+        };
+
+        (self.on_line)(&line);
+        self.collected.lines.push(line);
+    }
+}
@@ -0,0 +1,185 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::unix::io::{AsRawFd, OwnedFd};
+use std::process::{Child, ExitStatus};
+use std::sync::Once;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::{mem, ptr, thread};
+use std::time::{Duration, Instant};
+
+use super::output::{Collected, Collector, OutputLine, Stream};
+
+/// How long the streams of a killed unit are read for.  Processes which left
+/// its group, such as daemons it started, may hold them open for good.
+const KILL_GRACE: Duration = Duration::from_secs(1);
+
+/// Process groups of the units running at the moment, so that the signal
+/// which interrupts us can be passed on to them rather than leaving them
+/// running without us.  A table of atomics is all that a signal handler may
+/// safely look at.
+static GROUPS: [AtomicI32; 256] = [const { AtomicI32::new(0) }; 256];
+
+/// A locally spawned process which has exited, possibly having been killed
+/// for running too long
+pub struct Finished {
+    pub status: ExitStatus,
+    pub output: Collected,
+    pub timed_out: bool,
+}
+
+/// Waits for a child to exit, handing on each line of its output as it
+/// arrives.  If it runs longer than `timeout`, its whole process group is
+/// killed, so the child should have been spawned into a group of its own with
+/// its stdout and stderr piped.
+pub fn wait(
+    mut child: Child,
+    timeout: Option<Duration>,
+    on_line: &mut dyn FnMut(&OutputLine)
+) -> io::Result<Finished> {
+    let _group = Group::track(&child);
+    let mut deadline = timeout.map(|t| Instant::now() + t);
+    let mut pipes = Vec::new();
+
+    if let Some(stdout) = child.stdout.take() { pipes.push((Stream::Stdout, File::from(OwnedFd::from(stdout)))) }
+    if let Some(stderr) = child.stderr.take() { pipes.push((Stream::Stderr, File::from(OwnedFd::from(stderr)))) }
+
+    let mut collector = Collector::new(on_line);
+    let mut timed_out = false;
+    let mut buffer = [0; 4096];
+
+    // Both streams are read until they close, which is once the whole
+    // process group has exited or been killed
+    while !pipes.is_empty() {
+        let mut poll_fds = pipes.iter()
+            .map(|(_, pipe)| libc::pollfd { fd: pipe.as_raw_fd(), events: libc::POLLIN, revents: 0 })
+            .collect::<Vec<libc::pollfd>>();
+
+        let poll_timeout = match deadline {
+            Some(deadline) => deadline.saturating_duration_since(Instant::now())
+                .as_millis()
+                .min(libc::c_int::MAX as u128) as libc::c_int,
+            None => -1
+        };
+
+        let ready = unsafe {
+            libc::poll(poll_fds.as_mut_ptr(), poll_fds.len() as libc::nfds_t, poll_timeout)
+        };
+
+        if ready < 0 {
+            let error = io::Error::last_os_error();
+
+            if error.kind() == io::ErrorKind::Interrupted { continue }
+            return Err(error)
+        }
+
+        if ready == 0 {
+            // Whatever still holds the streams open after the grace period
+            // isn't in the unit's group, so they're given up on
+            if timed_out { break }
+
+            kill_group(&child);
+            timed_out = true;
+            deadline = Some(Instant::now() + KILL_GRACE);
+            continue
+        }
+
+        for (index, poll_fd) in poll_fds.iter().enumerate().rev() {
+            if poll_fd.revents == 0 { continue }
+
+            let (stream, pipe) = &mut pipes[index];
+
+            match pipe.read(&mut buffer) {
+                Ok(0) => { pipes.remove(index); },
+                Ok(n) => collector.push(*stream, &buffer[..n]),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(_) => { pipes.remove(index); }
+            }
+        }
+    }
+
+    drop(pipes);
+
+    // The child may have closed its streams without exiting
+    let status = loop {
+        if let Some(status) = child.try_wait()? { break status }
+
+        if !timed_out && deadline.is_some_and(|d| Instant::now() >= d) {
+            kill_group(&child);
+            timed_out = true;
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    };
+
+    Ok(Finished { status, output: collector.finish(), timed_out })
+}
+
+fn kill_group(child: &Child) {
+    unsafe { libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL); }
+}
+
+/// A child's process group, tracked for as long as it's waited upon
+struct Group {
+    slot: Option<usize>,
+}
+
+impl Group {
+    fn track(child: &Child) -> Group {
+        forward_interruptions();
+
+        let pgid = child.id() as i32;
+        let slot = GROUPS.iter()
+            .position(|slot| slot.compare_exchange(0, pgid, Ordering::SeqCst, Ordering::SeqCst).is_ok());
+
+        Group { slot }
+    }
+}
+
+impl Drop for Group {
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot {
+            GROUPS[slot].store(0, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Passes SIGINT and SIGTERM on to the groups of running units before letting
+/// them take their usual course.  Signals which whoever embeds us already
+/// handles or ignores are left alone.
+fn forward_interruptions() {
+    static INSTALL: Once = Once::new();
+
+    INSTALL.call_once(|| {
+        for signal in [libc::SIGINT, libc::SIGTERM].iter() {
+            unsafe {
+                let mut current: libc::sigaction = mem::zeroed();
+
+                if libc::sigaction(*signal, ptr::null(), &mut current) != 0 ||
+                    current.sa_sigaction != libc::SIG_DFL
+                {
+                    continue
+                }
+
+                let mut action: libc::sigaction = mem::zeroed();
+                action.sa_sigaction = forward_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+                libc::sigemptyset(&mut action.sa_mask);
+                libc::sigaction(*signal, &action, ptr::null_mut());
+            }
+        }
+    });
+}
+
+extern "C" fn forward_signal(signal: libc::c_int) {
+    for slot in GROUPS.iter() {
+        let pgid = slot.load(Ordering::SeqCst);
+
+        if pgid != 0 {
+            unsafe { libc::kill(-pgid, signal); }
+        }
+    }
+
+    unsafe {
+        libc::signal(signal, libc::SIG_DFL);
+        libc::raise(signal);
+    }
+}
@@ -1,20 +1,28 @@
+use std::sync::Arc;
+use std::time::Duration;
+
 use url::{Url};
 
 use crate::error::Error;
 use crate::operation::Operation;
-use crate::unit::Instance;
+use crate::unit::Job;
+use crate::events::{EventSink, Silent};
 use super::ExecutionResult;
 
 mod local;
 mod ssh;
 
-use self::ssh::SSH;
+use self::ssh::Ssh;
 use self::local::Local;
 use super::adapter::Adapter;
 use super::Executor;
 
 pub struct Target {
-    executor: Box<dyn Executor>
+    executor: Box<dyn Executor + Send>,
+    /// Applies to units which don't give a timeout of their own
+    default_timeout: Option<Duration>,
+    /// Receives units' output as it's printed
+    events: Arc<dyn EventSink>,
 }
 
 impl Target {
@@ -34,18 +42,37 @@ impl Target {
             }
         };
 
-        let mut executor: Box<dyn Executor> = match adapter_name {
+        let mut executor: Box<dyn Executor + Send> = match adapter_name {
             "local" => Box::new(Local::new(parsed_url_option.clone())),
-            "ssh" => Box::new(SSH::new(parsed_url_option.clone())),
+            "ssh" => Box::new(Ssh::new(parsed_url_option.clone())),
             adapter_name => Box::new(Adapter::try_new(adapter_name)?),
         };
 
         executor.init()?;
 
-        Ok(Target { executor: executor })
+        Ok(Target { executor, default_timeout: None, events: Arc::new(Silent) })
+    }
+
+    pub fn set_default_timeout(&mut self, timeout: Option<Duration>) {
+        self.default_timeout = timeout;
+    }
+
+    pub fn set_event_sink(&mut self, events: Arc<dyn EventSink>) {
+        self.events = events;
+    }
+
+    pub fn execute(&mut self, unit: &Job, operation: Operation) -> ExecutionResult {
+        let timeout = unit.definition.timeouts.get(operation).or(self.default_timeout);
+        let events = &self.events;
+
+        self.executor.execute(unit, operation, timeout, &mut |line| events.output_line(&unit.id, operation, line))
+    }
+
+    pub fn append_journal(&mut self, path: &str, entries: &str) -> Result<(), Error> {
+        self.executor.append_journal(path, entries)
     }
 
-    pub fn execute(&mut self, unit: &Instance, operation: Operation) -> ExecutionResult {
-        self.executor.execute(unit, operation)
+    pub fn read_journal(&mut self, path: &str) -> Result<String, Error> {
+        self.executor.read_journal(path)
     }
 }
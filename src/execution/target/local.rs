@@ -0,0 +1,165 @@
+use std::process::{Command, Stdio};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::str;
+use std::path::Path;
+use std::os::unix::process::CommandExt;
+use std::time::{Duration, Instant};
+
+use crate::unit::{Job, DefinitionType};
+use crate::error::Error;
+
+use super::super::{Executor, Operation, Execution, OutputLine};
+use super::super::{args_fd, process};
+
+use url::Url;
+
+pub struct Local {
+    url: Option<Url>,
+}
+
+impl Local {
+    pub fn new(url: Option<Url>) -> Local {
+        Local { url }
+    }
+}
+
+impl Executor for Local {
+    fn init(&mut self) -> Result<(), Error> {
+        match self.url {
+            Some(ref url) => match url.host() {
+                None => Ok(()),
+                Some(other) => match other.to_string().as_str() {
+                    "localhost" => Ok(()),
+                    _ => Err(Error::new(format!("Can not operate on host: {}", other)))
+                }
+            },
+            None => Ok(())
+        }
+    }
+
+    fn execute(
+        &mut self,
+        unit: &Job,
+        operation: Operation,
+        timeout: Option<Duration>,
+        on_line: &mut dyn FnMut(&OutputLine)
+    ) -> Result<Execution, Error> {
+        let definition = unit.definition.clone();
+        // Directory units may ship a `./test` executable alongside `./unit`
+        // which is run, without an operation argument, for the test operation
+        let (executable_name, operation_arg) = match operation {
+            Operation::Test => ("test", None),
+            other => ("unit", Some(other.to_str()))
+        };
+
+        let mut command = match &definition.definition_type {
+            DefinitionType::Executable => Command::new(&definition.path),
+            DefinitionType::Directory => {
+                let path = Path::new(&definition.path);
+                let executable_path = path.join(Path::new(executable_name));
+                let executable_path_str = executable_path.to_str().expect("Could not parse UTF8 path");
+                let canon = fs::canonicalize(executable_path_str).map_err(|e|
+                    Error::new(format!("Could not find {} for unit {}: {}",
+                        executable_path_str, definition.name, e))
+                )?;
+                let mut command = Command::new(canon.to_str().unwrap());
+
+                command.current_dir(&definition.path);
+
+                command
+            },
+            DefinitionType::Manifest => return Err(Error::new(format!(
+                "Manifest unit {} can not be executed", definition.name
+            )))
+        };
+
+        let env_iter = unit.id.args.vec.iter().map(|arg|
+            (arg.name.clone(), arg.value_string())
+        );
+
+        command
+            .args(operation_arg)
+            .envs(env_iter)
+            // A group of its own lets us kill everything the unit started if
+            // it times out, or pass on a Ctrl-C which only reaches us
+            .process_group(0)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        let args_pipe = args_fd::attach(&mut command, &unit.id.args)?;
+
+        let started = Instant::now();
+        let child = match args_pipe.spawn(&mut command) {
+            Ok(c) => c,
+            Err(e) => {
+                let msg = format!("Could not execute unit `{}`, path: {}, error: {:?}",
+                    definition.name,
+                    definition.path,
+                    e.kind()
+                );
+
+                return Err(Error::new(msg));
+            }
+        };
+
+        let external_signal_error = Error::new(format!(
+            "[{}] killed by external signal", definition.name
+        ));
+
+        let finished = process::wait(child, timeout, on_line).map_err(|_| external_signal_error.clone() )?;
+        let result = finished.output;
+
+        let exit_code = match (finished.status.code(), finished.timed_out) {
+            (Some(c), _) => c,
+            (None, true) => -1,
+            (None, false) => return Err(external_signal_error.clone())
+        };
+
+        fn stringify_bytes(bytes: Vec<u8>, stream_name: &str) -> Result<String, Error> {
+            let chars = str::from_utf8(&bytes).map_err(|_e|
+                Error::new(format!("Invalid UTF sequence in stream: {}", stream_name))
+            )?;
+
+            Ok(chars.to_string())
+        }
+
+        let execution = Execution {
+            unit_name: definition.name.clone(),
+            operation,
+            stdout: stringify_bytes(result.stdout, "stdout")?,
+            stderr: stringify_bytes(result.stderr, "stderr")?,
+            output: result.lines,
+            exit_code,
+            timed_out: match finished.timed_out {
+                true => timeout,
+                false => None
+            },
+            duration: started.elapsed()
+        };
+
+        Ok(execution)
+    }
+
+    fn append_journal(&mut self, path: &str, entries: &str) -> Result<(), Error> {
+        let journal_error = |e: io::Error|
+            Error::new(format!("Could not write journal {}: {}", path, e));
+
+        if let Some(dir) = Path::new(path).parent() {
+            fs::create_dir_all(dir).map_err(journal_error)?;
+        }
+
+        let mut file = OpenOptions::new().create(true).append(true).open(path)
+            .map_err(journal_error)?;
+
+        file.write_all(entries.as_bytes()).map_err(journal_error)
+    }
+
+    fn read_journal(&mut self, path: &str) -> Result<String, Error> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(contents),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+            Err(e) => Err(Error::new(format!("Could not read journal {}: {}", path, e)))
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use super::super::{Executor, Operation, Execution, OutputLine};
+use crate::unit::Job;
+use crate::error::{Error};
+
+use std::time::Duration;
+
+use url::Url;
+
+mod auth;
+mod transport;
+mod execute;
+mod connection;
+mod close_channel;
+mod journal;
+
+use connection::Connection;
+
+pub struct Ssh {
+    connection: Option<Connection>,
+    url: Option<Url>
+}
+
+impl Ssh { 
+    pub fn new(url: Option<Url>) -> Ssh {
+        Ssh { connection: None, url }
+    }
+
+    fn connection(&mut self) -> Result<&mut Connection, Error> {
+        match &mut self.connection {
+            None => Err(Error::new(
+                "Attempted to use unintialized SSH session.".to_string()
+            )),
+            Some(connection) => Ok(connection)
+        }
+    }
+}
+
+impl Executor for Ssh {
+    fn init(&mut self) -> Result<(), Error> {
+        let url = match self.url {
+            None => return Err(Error::new(
+                "Target URL must be provided for SSH".to_string()
+            )),
+            Some(ref x) => x
+        };
+
+        if self.connection.is_none() {
+            self.connection = Some(Connection::initialize(url)?);
+        }
+
+        Ok(())
+    }
+
+    fn execute(
+        &mut self,
+        unit: &Job,
+        operation: Operation,
+        timeout: Option<Duration>,
+        on_line: &mut dyn FnMut(&OutputLine)
+    ) -> Result<Execution, Error> {
+        match &mut self.connection {
+            None => Err(Error::new(
+                "Attempted to execute on unintialized SSH session.".to_string()
+            )),
+            Some(connection) => {
+                let execution = connection.execute(unit, operation, timeout, on_line)
+                    .map_err(|e| wrap_error!("SSH Execution Error: {}", e));
+
+                execution
+            }
+        }
+    }
+
+    fn append_journal(&mut self, path: &str, entries: &str) -> Result<(), Error> {
+        self.connection()?.append_journal(path, entries)
+            .map_err(|e| wrap_error!("SSH Journal Error: {}", e))
+    }
+
+    fn read_journal(&mut self, path: &str) -> Result<String, Error> {
+        self.connection()?.read_journal(path)
+            .map_err(|e| wrap_error!("SSH Journal Error: {}", e))
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+use crate::ui::prompt_ssh_password;
+use url::Url;
+use ssh2::Session;
+use log::info;
+
+use crate::error::{Error, BoxedResult};
+
+pub fn auth(session: &mut Session, url: &Url) -> Result<(), Error> {
+    let username = match url.username() {
+        "" => return Err(
+            Error::new(format!("Target `{:?}` must specify a username", url))
+        ),
+        v => v
+    };
+
+    fn auth_via_agent(session: &mut Session, username: &str) -> BoxedResult<()> {
+        let mut agent = session.agent().map_err(|e| Error::new(e.to_string()))?;
+        agent.connect()?;
+        agent.list_identities()?;
+        let identities = agent.identities().map_err(Box::new)?;
+
+        for identity in identities {
+            match agent.userauth(username, &identity) {
+                Err(_) => (),
+                Ok(_) => return Ok(())
+            }
+        }
+
+        Err(Box::new(Error::new("No identiy in agent could authenticate".to_string())))
+    }
+
+    fn auth_via_password(
+        session: &mut Session,
+        url: &Url,
+        username: &str
+    ) -> BoxedResult<()> {
+        let host = match url.host() {
+            None => return Err(Box::new(Error::new(format!("URL {} must specify a host!", url)))),
+            Some(x) => x.to_string()
+        };
+
+        let password = password(username, &host)?;
+        match session.userauth_password(username, &password) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                forget_password(username, &host);
+                Err(Box::new(e))
+            }
+        }
+    }
+
+    match auth_via_agent(session, username) {
+        Ok(_) => return Ok(()),
+        Err(e) => info!("SSH agent auth to {:?} failed: {}", url, e)
+    }
+
+    match auth_via_password(session, url, username) {
+        Ok(_) => return Ok(()),
+        Err(e) => info!("SSH password auth to {:?} failed: {}", url, e)
+    }
+
+    Err(Error::new(format!("SSH Agent and Password auth to {:?} failed", url)))
+}
+
+/// Passwords given so far, by user@host
+fn passwords() -> &'static Mutex<HashMap<String, String>> {
+    static PASSWORDS: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+
+    PASSWORDS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Prompts for a host's password the first time it's needed only, as every
+/// job opens a connection of its own.  The lock is held while prompting so
+/// that prompts for hosts connected to at once don't interleave.
+fn password(username: &str, host: &str) -> Result<String, Error> {
+    let mut passwords = passwords().lock().unwrap_or_else(|e| e.into_inner());
+    let key = format!("{}@{}", username, host);
+
+    if let Some(password) = passwords.get(&key) {
+        return Ok(password.clone())
+    }
+
+    let password = prompt_ssh_password(username, host)?;
+    passwords.insert(key, password.clone());
+
+    Ok(password)
+}
+
+fn forget_password(username: &str, host: &str) {
+    passwords().lock().unwrap_or_else(|e| e.into_inner())
+        .remove(&format!("{}@{}", username, host));
+}
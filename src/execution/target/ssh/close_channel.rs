@@ -0,0 +1,154 @@
+use std::io::{self, prelude::*};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use ssh2::{Channel, Session};
+use log::debug;
+
+use crate::error::Error;
+use crate::execution::OutputLine;
+use crate::execution::output::{Collected, Collector, Stream};
+
+#[derive(Debug)]
+pub struct ChannelResult {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_status: i32,
+}
+
+pub fn close_channel(channel: &mut Channel) -> Result<ChannelResult, Error> {
+    channel.send_eof().map_err(|e|
+        wrap_error!("Failed to send EOF on channel: {}", e)
+    )?;
+
+    channel.close().map_err(|e|
+        wrap_error!("Failed to send close on channel: {}", e)
+    )?;
+
+    let mut output = String::new();
+    let mut stderr = String::new();
+
+    loop {
+        channel.wait_eof().map_err(|e| {
+            debug!("Wait EOF on Channel error: {:?}", e);
+            Error::new(format!("Failed to wait for EOF on channel: {}", e))
+        })?;
+
+        channel.read_to_string(&mut output).map_err(|e| {
+            debug!("Command output read error: {:?}", e);
+            Error::new(format!("Failed to read unit output: {}", e))
+        })?;
+
+        channel.stderr().read_to_string(&mut stderr).map_err(|e| {
+            debug!("Command stderr read error: {:?}", e);
+            Error::new(format!("Failed to read unit stderr: {}", e))
+        })?;
+
+        if channel.eof() { break };
+    }
+
+    let exit_code = channel.exit_status()
+        .map_err(|e| Error::new(format!(
+            "Failed to get exit code: {}", e
+        )))?;
+
+    debug!("eof: {:?}", channel.wait_eof());
+
+    let channel_result = ChannelResult {
+        stdout: output,
+        stderr,
+        exit_status: exit_code
+    };
+
+    Ok(channel_result)
+}
+
+pub struct StreamResult {
+    pub output: Collected,
+    pub exit_status: i32,
+    /// Set if the deadline passed before the unit finished, in which case
+    /// there's no exit status
+    pub timed_out: bool,
+}
+
+/// Closes the channel as `close_channel` does, but reads the unit's output
+/// as it arrives until it exits or the deadline passes.  The session is made
+/// non-blocking for the duration, so that neither stream holds up the other
+/// and the deadline can be kept.
+pub fn stream_channel(
+    session: &Session,
+    channel: &mut Channel,
+    deadline: Option<Instant>,
+    on_line: &mut dyn FnMut(&OutputLine)
+) -> Result<StreamResult, Error> {
+    channel.send_eof().map_err(|e|
+        wrap_error!("Failed to send EOF on channel: {}", e)
+    )?;
+
+    session.set_blocking(false);
+    let read_result = read_output(channel, deadline, on_line);
+    session.set_blocking(true);
+
+    let (output, timed_out) = read_result?;
+
+    if timed_out {
+        return Ok(StreamResult { output, exit_status: -1, timed_out: true })
+    }
+
+    channel.close().map_err(|e|
+        wrap_error!("Failed to send close on channel: {}", e)
+    )?;
+
+    let exit_code = channel.exit_status()
+        .map_err(|e| Error::new(format!(
+            "Failed to get exit code: {}", e
+        )))?;
+
+    debug!("eof: {:?}", channel.wait_eof());
+
+    Ok(StreamResult { output, exit_status: exit_code, timed_out: false })
+}
+
+fn read_output(
+    channel: &mut Channel,
+    deadline: Option<Instant>,
+    on_line: &mut dyn FnMut(&OutputLine)
+) -> Result<(Collected, bool), Error> {
+    let mut collector = Collector::new(on_line);
+    let mut buffer = [0; 4096];
+
+    loop {
+        let mut read_any = false;
+
+        for stream in [Stream::Stdout, Stream::Stderr].iter() {
+            let mut reader = match stream {
+                Stream::Stdout => channel.stream(0),
+                Stream::Stderr => channel.stderr(),
+            };
+
+            match reader.read(&mut buffer) {
+                Ok(0) => (),
+                Ok(n) => {
+                    collector.push(*stream, &buffer[..n]);
+                    read_any = true;
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => (),
+                Err(e) => {
+                    debug!("Command {} read error: {:?}", stream.to_str(), e);
+                    return Err(Error::new(format!("Failed to read unit {}: {}", stream.to_str(), e)))
+                }
+            }
+        }
+
+        if read_any { continue }
+        if channel.eof() { break }
+
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            return Ok((collector.finish(), true))
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    Ok((collector.finish(), false))
+}
@@ -0,0 +1,106 @@
+use std::collections::HashSet;
+use std::net::TcpStream;
+use std::time::Duration;
+
+use url::Url;
+use ssh2::Session;
+
+use crate::error::{BoxedResult, Error};
+use crate::unit::Job;
+
+use super::auth;
+use super::execute;
+use super::transport;
+use super::journal;
+
+use crate::operation::Operation;
+use crate::execution::{Execution, OutputLine};
+
+pub struct Connection {
+    session: Session,
+    /// Where this connection transports units to on the target
+    remote_root: String,
+    /// Names of the units already transported.  Every instance of a unit
+    /// shares the one copy.
+    transported_units: HashSet<String>,
+}
+
+impl Connection {
+    pub fn initialize(url: &Url) -> Result<Connection, Error> {
+        let addrs = url.socket_addrs(||
+            match url.scheme() {
+                "ssh" => Some(22),
+                _ => None
+            }
+        ).map_err(|e| Error::new(format!("Address Resolution Error on `{}`: {}", url, e)))?;
+
+        let tcp = TcpStream::connect(&*addrs)
+            .map_err(|e| Error::new(format!("TCP Error: {}", e)))?;
+
+        let mut session = Session::new().unwrap();
+        session.set_tcp_stream(tcp);
+        session.handshake().map_err(|e| Error::new(format!("SSH Handshake Error: {}", e)))?;
+
+        auth::auth(&mut session, url)?;
+
+        let remote_root = transport::create_remote_root(&session)
+            .map_err(|e| wrap_error!("SSH Transport Error: {}", e))?;
+
+        Ok(Connection {
+            session,
+            remote_root,
+            transported_units: HashSet::new()
+        })
+    }
+
+    fn remote_path(&self, unit: &Job) -> String {
+        format!("{}/{}", self.remote_root, unit.definition.name)
+    }
+
+    pub fn transport(&mut self, unit: &Job) -> BoxedResult<()> {
+        if self.transported_units.contains(&unit.definition.name) {
+            return Ok(())
+        }
+
+        transport::transport(unit, &self.remote_path(unit), &self.session)?;
+        self.transported_units.insert(unit.definition.name.clone());
+        Ok(())
+    }
+
+    pub fn execute(
+        &mut self,
+        unit: &Job,
+        operation: Operation,
+        timeout: Option<Duration>,
+        on_line: &mut dyn FnMut(&OutputLine)
+    ) -> BoxedResult<Execution> {
+        self.transport(unit)?;
+
+        let remote_path = self.remote_path(unit);
+        let pid_path = format!("{}/.pid", self.remote_root);
+
+        execute::execute(
+            unit,
+            &self.session,
+            remote_path,
+            &pid_path,
+            operation,
+            timeout,
+            on_line
+        )
+    }
+
+    pub fn append_journal(&mut self, path: &str, entries: &str) -> BoxedResult<()> {
+        journal::append(&self.session, path, entries)
+    }
+
+    pub fn read_journal(&mut self, path: &str) -> BoxedResult<String> {
+        journal::read(&self.session, path)
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        transport::remove_remote_root(&self.remote_root, &self.session)
+    }
+}
@@ -0,0 +1,125 @@
+use crate::error::{Error, BoxedResult};
+use crate::unit::{Job, DefinitionType};
+use crate::operation::Operation;
+use crate::execution::{Execution, OutputLine};
+use crate::execution::args_fd::{ARGS_FD, ARGS_FD_VAR};
+
+use std::borrow::Cow;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use log::debug;
+use ssh2::Session;
+use shell_escape::unix::escape;
+
+use super::close_channel::{close_channel, stream_channel};
+
+/// Executes the unit at `unit_path` on the target.  The shell running it
+/// notes its PID, which is also its process group's since sshd starts it in a
+/// session of its own, in `pid_path` so that the unit can be killed should it
+/// time out.
+pub fn execute(
+    unit: &Job,
+    session: &Session,
+    unit_path: String,
+    pid_path: &str,
+    operation: Operation,
+    timeout: Option<Duration>,
+    on_line: &mut dyn FnMut(&OutputLine)
+) -> BoxedResult<Execution> {
+    let mut channel = session.channel_session().map_err(|e| {
+        wrap_error!("Channel Initialization Error: {}", e)
+    })?;
+
+    let arg_str = unit.id.args.vec.iter().map(|arg|
+        format!("{}={}", arg.name, escape(Cow::from(arg.value_string())))
+    ).collect::<Vec<String>>().join(" ");
+
+    let args_document = unit.id.args.to_toml()?;
+
+    // The arguments document is written to the channel's stdin, which the
+    // unit receives on the args descriptor rather than its own stdin
+    let arg_str = format!("{}={} {}", ARGS_FD_VAR, ARGS_FD, arg_str);
+    let redirect = format!("{}<&0 </dev/null", ARGS_FD);
+
+    let command_string = match unit.definition.definition_type {
+        DefinitionType::Executable => format!("{} {} {} {}",
+            arg_str,
+            unit_path,
+            operation.to_str(),
+            redirect),
+        DefinitionType::Directory => match operation {
+            Operation::Test => format!("cd {}; {} ./test {}", unit_path, arg_str, redirect),
+            _ => format!("cd {}; {} ./unit {} {}",
+                unit_path,
+                arg_str,
+                operation.to_str(),
+                redirect)
+        },
+        DefinitionType::Manifest => return Err(Box::new(Error::new(format!(
+            "Manifest unit {} can not be executed", unit.definition.name
+        ))))
+    };
+
+    let command_string = format!("echo $$ > {}; {}", escape(Cow::from(pid_path)), command_string);
+
+    debug!("command string: {}", command_string);
+
+    let started = Instant::now();
+
+    channel.exec(command_string.as_str()).map_err(|e|
+        wrap_error!("Failed to execute unit: {}", e)
+    )?;
+
+    // Units may exit without reading their arguments, so a failed write is
+    // not an error in itself
+    let _ = channel.write_all(args_document.as_bytes());
+
+    let result = stream_channel(session, &mut channel, timeout.map(|t| started + t), on_line)?;
+
+    // Without a PTY, closing the channel doesn't signal the unit, so it's
+    // killed from a channel of its own
+    if result.timed_out {
+        kill(session, pid_path)?;
+        drop(channel)
+    }
+
+    let execution = Execution {
+        unit_name: unit.definition.name.clone(),
+        operation,
+        exit_code: result.exit_status,
+        stdout: String::from_utf8_lossy(&result.output.stdout).to_string(),
+        stderr: String::from_utf8_lossy(&result.output.stderr).to_string(),
+        output: result.output.lines,
+        timed_out: match result.timed_out {
+            true => timeout,
+            false => None
+        },
+        duration: started.elapsed()
+    };
+
+    debug!("execution: {:?}", execution);
+
+    Ok(execution)
+}
+
+/// Kills the process group of the unit whose shell noted its PID in
+/// `pid_path`, or the shell alone if that fails
+fn kill(session: &Session, pid_path: &str) -> BoxedResult<()> {
+    let pid = format!("$(cat {})", escape(Cow::from(pid_path)));
+    let command_string = format!("kill -s KILL -- -{} 2>/dev/null || kill -s KILL {}", pid, pid);
+
+    let mut channel = session.channel_session().map_err(|e| {
+        wrap_error!("Channel Initialization Error: {}", e)
+    })?;
+
+    channel.exec(command_string.as_str()).map_err(|e|
+        wrap_error!("Failed to kill timed out unit: {}", e)
+    )?;
+
+    let channel_result = close_channel(&mut channel)?;
+
+    debug!("kill: {:?}", channel_result);
+
+    Ok(())
+}
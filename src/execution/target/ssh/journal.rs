@@ -0,0 +1,52 @@
+use std::borrow::Cow;
+use std::io::Write;
+
+use shell_escape::unix::escape;
+use ssh2::Session;
+
+use crate::error::{Error, BoxedResult};
+
+use super::close_channel::{close_channel, ChannelResult};
+
+pub fn append(session: &Session, path: &str, entries: &str) -> BoxedResult<()> {
+    let escaped_path = escape(Cow::from(path));
+    let command_string = format!("mkdir -p \"$(dirname {})\" && cat >> {}",
+        escaped_path,
+        escaped_path);
+
+    let channel_result = run(session, &command_string, entries)?;
+
+    if channel_result.exit_status != 0 {
+        return Err(Box::new(Error::new(format!(
+            "Could not write journal {}: {}", path, channel_result.stderr.trim_end()
+        ))))
+    }
+
+    Ok(())
+}
+
+pub fn read(session: &Session, path: &str) -> BoxedResult<String> {
+    let escaped_path = escape(Cow::from(path));
+    let command_string = format!("if [ -e {} ]; then cat {}; fi", escaped_path, escaped_path);
+
+    let channel_result = run(session, &command_string, "")?;
+
+    if channel_result.exit_status != 0 {
+        return Err(Box::new(Error::new(format!(
+            "Could not read journal {}: {}", path, channel_result.stderr.trim_end()
+        ))))
+    }
+
+    Ok(channel_result.stdout)
+}
+
+fn run(session: &Session, command_string: &str, input: &str) -> BoxedResult<ChannelResult> {
+    let mut channel = session.channel_session().map_err(|e|
+        wrap_error!("Channel Initialization Error: {}", e)
+    )?;
+
+    channel.exec(command_string)?;
+    channel.write_all(input.as_bytes())?;
+
+    Ok(close_channel(&mut channel)?)
+}
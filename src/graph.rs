@@ -0,0 +1,151 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::error::Error;
+use crate::resolver::InstanceVec;
+use crate::ui::json::instance_args;
+use crate::unit::{ApplicationState, Instance, Outcome};
+
+/// How `graph` renders the resolved instances
+#[derive(Clone, Copy)]
+pub enum Format {
+    Dot,
+    Mermaid,
+    Json
+}
+
+#[derive(Serialize)]
+struct Graph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+}
+
+#[derive(Serialize)]
+struct Node {
+    id: String,
+    unit: String,
+    args: Map<String, Value>,
+    /// Only known if the graph's units were checked
+    state: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+struct Edge {
+    /// The dependent instance
+    from: String,
+    /// The instance it depends upon
+    to: String,
+}
+
+/// Renders the instance graph with an edge from each instance to each of the
+/// instances it depends upon.  Nodes are coloured by their check's result if
+/// they were checked.
+pub fn render(instances: &InstanceVec, format: Format) -> Result<String, Error> {
+    let instances = instances.iter().map(|rc| rc.borrow()).collect::<Vec<_>>();
+
+    match format {
+        Format::Dot => {
+            let mut output = "digraph sysunit {\n  node [shape=box];\n".to_string();
+
+            for instance in instances.iter() {
+                let fill = match state(instance) {
+                    Some(state) => format!(", style=filled, fillcolor=\"{}\"", colour(state)),
+                    None => String::new()
+                };
+
+                output.push_str(&format!("  \"{}\" [label=\"{}\"{}];\n",
+                    dot_escape(&instance.id.signature()),
+                    dot_escape(&instance.id.to_string()),
+                    fill));
+            }
+
+            for instance in instances.iter() {
+                for dependency in instance.dependencies.iter() {
+                    output.push_str(&format!("  \"{}\" -> \"{}\";\n",
+                        dot_escape(&instance.id.signature()),
+                        dot_escape(dependency)));
+                }
+            }
+
+            output.push('}');
+            Ok(output)
+        },
+        Format::Mermaid => {
+            // Signatures identify nodes elsewhere, but Mermaid is particular
+            // about the characters in its ids, so nodes are numbered instead
+            let index_of = |signature: &str| instances.iter()
+                .position(|instance| instance.id.signature() == signature);
+            let mut output = "graph TD\n".to_string();
+
+            for (index, instance) in instances.iter().enumerate() {
+                output.push_str(&format!("  n{}[\"{}\"]\n",
+                    index,
+                    instance.id.to_string().replace('"', "#quot;")));
+            }
+
+            for (index, instance) in instances.iter().enumerate() {
+                for dependency in instance.dependencies.iter().filter_map(|d| index_of(d)) {
+                    output.push_str(&format!("  n{} --> n{}\n", index, dependency));
+                }
+            }
+
+            for node_state in ["applied", "not applied", "timed out"].iter() {
+                let members = instances.iter().enumerate()
+                    .filter(|(_, instance)| state(instance) == Some(*node_state))
+                    .map(|(index, _)| format!("n{}", index))
+                    .collect::<Vec<String>>();
+
+                if members.is_empty() { continue }
+
+                let class = node_state.replace(' ', "_");
+                output.push_str(&format!("  classDef {} fill:{}\n", class, colour(node_state)));
+                output.push_str(&format!("  class {} {}\n", members.join(","), class));
+            }
+
+            Ok(output.trim_end().to_string())
+        },
+        Format::Json => {
+            let graph = Graph {
+                nodes: instances.iter()
+                    .map(|instance| Node {
+                        id: instance.id.signature(),
+                        unit: instance.id.name.clone(),
+                        args: instance_args(&instance.id),
+                        state: state(instance),
+                    })
+                    .collect(),
+                edges: instances.iter()
+                    .flat_map(|instance| instance.dependencies.iter().map(move |dependency| Edge {
+                        from: instance.id.signature(),
+                        to: dependency.clone(),
+                    }))
+                    .collect(),
+            };
+
+            serde_json::to_string_pretty(&graph).map_err(|e|
+                wrap_error!("Could not render graph: {}", e)
+            )
+        }
+    }
+}
+
+fn state(instance: &Instance) -> Option<&'static str> {
+    match (&instance.application_state, &instance.outcome) {
+        (Some(ApplicationState::Applied), _) => Some("applied"),
+        (Some(ApplicationState::NotApplied(_)), _) => Some("not applied"),
+        (None, Some(Outcome::TimedOut(_))) => Some("timed out"),
+        _ => None
+    }
+}
+
+fn colour(state: &str) -> &'static str {
+    match state {
+        "applied" => "#b7e4b0",
+        "not applied" => "#f4b6b6",
+        _ => "#d3d3d3"
+    }
+}
+
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
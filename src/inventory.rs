@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::fs;
+
+use serde::Deserialize;
+use toml::value::Table;
+use url::Url;
+
+use crate::error::Error;
+use crate::execution::Target;
+use crate::unit::ArgSet;
+
+/// Named hosts and the groups they're organised into:
+///
+/// ```toml
+/// [hosts.web1]
+/// url = "ssh://root@10.0.0.1"
+/// args = { worker_count = 4 }
+///
+/// [groups.web]
+/// hosts = ["web1", "web2"]
+/// args = { server_name = "example.com" }
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Inventory {
+    #[serde(default)]
+    pub hosts: HashMap<String, HostEntry>,
+    #[serde(default)]
+    pub groups: HashMap<String, GroupEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HostEntry {
+    /// Target URL, the local machine if omitted
+    pub url: Option<String>,
+    pub adapter: Option<String>,
+    #[serde(default)]
+    pub args: Table,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GroupEntry {
+    pub hosts: Vec<String>,
+    #[serde(default)]
+    pub args: Table,
+}
+
+/// Everything needed to operate upon one target
+#[derive(Clone, Debug)]
+pub struct TargetSpec {
+    /// Identifies the target in output
+    pub name: String,
+    pub url: Option<String>,
+    pub adapter: Option<String>,
+    /// Merged into the arguments of the root units run against this target
+    pub default_args: ArgSet,
+}
+
+impl TargetSpec {
+    pub fn from_url(url: Option<&str>, adapter: Option<&str>) -> TargetSpec {
+        TargetSpec {
+            name: host_label(url),
+            url: url.map(|u| u.to_string()),
+            adapter: adapter.map(|a| a.to_string()),
+            default_args: ArgSet::new(),
+        }
+    }
+
+    pub fn connect(&self) -> Result<Target, Error> {
+        Target::try_new(self.url.as_deref(), self.adapter.as_deref())
+    }
+}
+
+impl Inventory {
+    pub fn load(path: &str) -> Result<Inventory, Error> {
+        let document = fs::read_to_string(path).map_err(|e|
+            Error::new(format!("Could not read inventory {}: {}", path, e))
+        )?;
+
+        toml::from_str(&document).map_err(|e|
+            Error::new(format!("Inventory {} is invalid: {}", path, e))
+        )
+    }
+
+    /// Finds the targets named by a group or host.  A host's own arguments
+    /// take precedence over those of the group it was selected through.
+    pub fn select(&self, name: &str) -> Result<Vec<TargetSpec>, Error> {
+        if let Some(group) = self.groups.get(name) {
+            return group.hosts.iter()
+                .map(|host_name| self.target_spec(host_name, &group.args))
+                .collect()
+        }
+
+        if self.hosts.contains_key(name) {
+            return Ok(vec![self.target_spec(name, &Table::new())?])
+        }
+
+        Err(Error::new(format!("No group or host named `{}` in inventory", name)))
+    }
+
+    fn target_spec(&self, host_name: &str, group_args: &Table) -> Result<TargetSpec, Error> {
+        let host = self.hosts.get(host_name).ok_or_else(||
+            Error::new(format!("Inventory host `{}` is not defined", host_name))
+        )?;
+
+        let mut args = group_args.clone();
+        args.extend(host.args.clone());
+
+        Ok(TargetSpec {
+            name: host_name.to_string(),
+            url: host.url.clone(),
+            adapter: host.adapter.clone(),
+            default_args: ArgSet::from_table(&args),
+        })
+    }
+}
+
+/// Names a target in output by its host, and port if one was given
+fn host_label(target_url: Option<&str>) -> String {
+    let url_str = match target_url {
+        Some(u) => u,
+        None => return "localhost".to_string()
+    };
+
+    match Url::parse(url_str) {
+        Ok(ref url) if url.host_str().is_some() => match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap(), port),
+            None => url.host_str().unwrap().to_string()
+        },
+        _ => url_str.to_string()
+    }
+}
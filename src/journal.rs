@@ -0,0 +1,155 @@
+use std::fs;
+use std::path::Path;
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::Utc;
+use crypto::sha1::Sha1;
+use crypto::digest::Digest;
+use serde::{Deserialize, Serialize};
+use toml::value::{Datetime, Table};
+
+use crate::error::Error;
+use crate::operation::Operation;
+use crate::unit::{Definition, DefinitionType, Instance, Outcome};
+
+/// A record of one apply or rollback of an instance.  Journals are TOML
+/// documents of `entry` tables, which lets new entries simply be appended:
+///
+/// ```toml
+/// [[entry]]
+/// unit = "nginx"
+/// operation = "apply"
+/// result = "converged"
+/// content_hash = "5f3c..."
+/// timestamp = 2021-03-04T05:06:07Z
+/// run_id = "6040699f-1a2b"
+///
+/// [entry.args]
+/// port = 80
+/// ```
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub unit: String,
+    pub operation: String,
+    pub result: String,
+    /// SHA1 of the unit's files, to tell which version of it ran
+    pub content_hash: String,
+    pub timestamp: Datetime,
+    /// Shared by every entry written by the same sysunit invocation
+    pub run_id: String,
+    /// Kept last, since TOML needs tables to follow plain values
+    pub args: Table,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Journal {
+    #[serde(default)]
+    entry: Vec<Entry>,
+}
+
+impl Entry {
+    /// Records the outcome of an operation on an instance, if it was executed
+    pub(crate) fn record(instance: &Instance, operation: Operation, run_id: &str) -> Result<Option<Entry>, Error> {
+        let result = match &instance.outcome {
+            Some(Outcome::Skipped) | None => return Ok(None),
+            Some(outcome) => outcome.to_str(),
+        };
+
+        let args = instance.id.args.vec.iter()
+            .map(|arg| (arg.name.clone(), arg.value.clone()))
+            .collect::<Table>();
+
+        Ok(Some(Entry {
+            unit: instance.id.name.clone(),
+            operation: operation.to_str().to_string(),
+            result: result.to_string(),
+            content_hash: content_hash(&instance.definition)?,
+            timestamp: now(),
+            run_id: run_id.to_string(),
+            args,
+        }))
+    }
+}
+
+/// Renders entries as a fragment which can be appended to a journal
+pub fn render(entries: Vec<Entry>) -> Result<String, Error> {
+    let rendered = toml::to_string(&Journal { entry: entries }).map_err(|e|
+        wrap_error!("Could not render journal entries: {}", e)
+    )?;
+
+    // Keeps batches apart from whatever is appended after them
+    Ok(format!("{}\n", rendered))
+}
+
+pub fn parse(document: &str) -> Result<Vec<Entry>, Error> {
+    let journal: Journal = toml::from_str(document).map_err(|e|
+        wrap_error!("Journal parse error: {}", e)
+    )?;
+
+    Ok(journal.entry)
+}
+
+/// Identifies this invocation of sysunit in the journals it writes
+pub fn run_id() -> String {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    format!("{:x}-{:x}", seconds, process::id())
+}
+
+/// Hashes a unit's executable, or every file within a directory unit
+fn content_hash(definition: &Definition) -> Result<String, Error> {
+    let mut hasher = Sha1::new();
+    let path = Path::new(&definition.path);
+
+    match definition.definition_type {
+        DefinitionType::Directory => hash_directory(&mut hasher, path, path)?,
+        _ => hash_file(&mut hasher, path)?
+    }
+
+    Ok(hasher.result_str())
+}
+
+fn hash_directory(hasher: &mut Sha1, root: &Path, dir: &Path) -> Result<(), Error> {
+    let read_error = |e: std::io::Error|
+        Error::new(format!("Could not read {}: {}", dir.to_string_lossy(), e));
+
+    let mut paths = fs::read_dir(dir).map_err(read_error)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(read_error)?;
+
+    // Directory listings come in no particular order
+    paths.sort();
+
+    for path in paths.iter() {
+        let relative_path = path.strip_prefix(root).unwrap_or(path);
+        hasher.input_str(&relative_path.to_string_lossy());
+
+        match path.is_dir() {
+            true => hash_directory(hasher, root, path)?,
+            false => hash_file(hasher, path)?
+        }
+    }
+
+    Ok(())
+}
+
+fn hash_file(hasher: &mut Sha1, path: &Path) -> Result<(), Error> {
+    let contents = fs::read(path).map_err(|e|
+        Error::new(format!("Could not read {}: {}", path.to_string_lossy(), e))
+    )?;
+
+    hasher.input(&contents);
+
+    Ok(())
+}
+
+/// The current time in UTC, as a TOML datetime
+fn now() -> Datetime {
+    Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string()
+        .parse()
+        .expect("Invalid datetime")
+}
@@ -0,0 +1,221 @@
+use std::fs;
+use std::time::Duration;
+
+use crate::error::Error;
+use crate::execution::Execution;
+use crate::operation::Operation;
+use crate::resolver::InstanceVec;
+use crate::unit::{ApplicationState, Instance, Outcome};
+
+/// The instances of one target as a JUnit test suite, each one a test case.
+/// This lets CI servers show runs, and compliance checks in particular,
+/// alongside the rest of their test results.
+pub struct Suite {
+    name: String,
+    cases: Vec<Case>,
+}
+
+struct Case {
+    name: String,
+    duration: Duration,
+    result: CaseResult,
+    /// Output of every execution of the instance's unit
+    output: String,
+}
+
+enum CaseResult {
+    Passed,
+    Failed(String),
+    Skipped,
+    Error(String),
+}
+
+impl CaseResult {
+    fn is_failure(&self) -> bool {
+        matches!(self, CaseResult::Failed(_))
+    }
+
+    fn is_error(&self) -> bool {
+        matches!(self, CaseResult::Error(_))
+    }
+
+    fn is_skipped(&self) -> bool {
+        matches!(self, CaseResult::Skipped)
+    }
+}
+
+impl Suite {
+    /// Records the instances of a finished run against a target
+    pub fn record(host: &str, instances: &InstanceVec, operation: Operation) -> Suite {
+        Suite {
+            name: host.to_string(),
+            cases: instances.iter()
+                .map(|rc| Case::record(&rc.borrow(), operation))
+                .collect(),
+        }
+    }
+
+    /// Stands in for a target which couldn't be operated upon at all
+    pub fn error(host: &str, error: &Error) -> Suite {
+        Suite {
+            name: host.to_string(),
+            cases: vec![Case {
+                name: "sysunit".to_string(),
+                duration: Duration::from_secs(0),
+                result: CaseResult::Error(error.msg.clone()),
+                output: String::new(),
+            }],
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn count<F>(&self, predicate: F) -> usize where F: Fn(&CaseResult) -> bool {
+        self.cases.iter().filter(|case| predicate(&case.result)).count()
+    }
+
+    fn duration(&self) -> Duration {
+        self.cases.iter().map(|case| case.duration).sum()
+    }
+
+    fn render(&self) -> String {
+        let mut output = format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
+            escape(&self.name),
+            self.cases.len(),
+            self.count(CaseResult::is_failure),
+            self.count(CaseResult::is_error),
+            self.count(CaseResult::is_skipped),
+            self.duration().as_secs_f64());
+
+        for case in self.cases.iter() {
+            output.push_str(&case.render(&self.name));
+        }
+
+        output.push_str("  </testsuite>\n");
+        output
+    }
+}
+
+impl Case {
+    fn record(instance: &Instance, operation: Operation) -> Case {
+        let result = match (&instance.outcome, instance.test_passed) {
+            (Some(Outcome::Failed(exit_code)), _) =>
+                CaseResult::Failed(format!("{} exited with {}", operation.to_str(), exit_code)),
+            (Some(Outcome::NotConverged(reason)), _) =>
+                CaseResult::Failed(with_reason("did not converge", reason)),
+            (Some(Outcome::TimedOut(timeout)), _) =>
+                CaseResult::Failed(format!("timed out after {}s", timeout.as_secs())),
+            (Some(Outcome::Skipped), _) => CaseResult::Skipped,
+            (_, Some(false)) => CaseResult::Failed("test failed".to_string()),
+            // Only runs which do no more than check treat an instance which
+            // isn't applied as a failure; applies and rollbacks expect them.
+            (None, _) => match (operation, &instance.application_state) {
+                (Operation::Check, Some(ApplicationState::NotApplied(reason))) |
+                (Operation::Drift, Some(ApplicationState::NotApplied(reason))) =>
+                    CaseResult::Failed(with_reason("check did not report ok", reason)),
+                _ => CaseResult::Passed
+            },
+            _ => CaseResult::Passed
+        };
+
+        Case {
+            name: instance.id.to_string(),
+            duration: instance.executions.iter().map(|e| e.duration).sum(),
+            result,
+            output: instance.executions.iter().map(execution_output).collect(),
+        }
+    }
+
+    fn render(&self, suite_name: &str) -> String {
+        let attributes = format!("name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+            escape(&self.name),
+            escape(suite_name),
+            self.duration.as_secs_f64());
+
+        let body = match &self.result {
+            CaseResult::Passed => return format!("    <testcase {} />\n", attributes),
+            CaseResult::Skipped => "      <skipped />\n".to_string(),
+            CaseResult::Failed(message) => format!("      <failure message=\"{}\">{}</failure>\n",
+                escape(message), escape(&self.output)),
+            CaseResult::Error(message) => format!("      <error message=\"{}\" />\n",
+                escape(message)),
+        };
+
+        format!("    <testcase {}>\n{}    </testcase>\n", attributes, body)
+    }
+}
+
+/// Renders the suites of every target as a single JUnit XML document
+pub fn render(suites: &[Suite], operation: Operation) -> String {
+    let mut output = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites name=\"sysunit {}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\">\n",
+        operation.to_str(),
+        suites.iter().map(|s| s.cases.len()).sum::<usize>(),
+        suites.iter().map(|s| s.count(CaseResult::is_failure)).sum::<usize>(),
+        suites.iter().map(|s| s.count(CaseResult::is_error)).sum::<usize>(),
+        suites.iter().map(|s| s.duration()).sum::<Duration>().as_secs_f64());
+
+    for suite in suites.iter() {
+        output.push_str(&suite.render());
+    }
+
+    output.push_str("</testsuites>\n");
+    output
+}
+
+pub fn write(path: &str, suites: &[Suite], operation: Operation) -> Result<(), Error> {
+    fs::write(path, render(suites, operation)).map_err(|e|
+        Error::new(format!("Could not write report {}: {}", path, e))
+    )
+}
+
+fn with_reason(message: &str, reason: &str) -> String {
+    match reason.trim_end() {
+        "" => message.to_string(),
+        reason => format!("{}: {}", message, reason)
+    }
+}
+
+fn execution_output(execution: &Execution) -> String {
+    let mut output = format!("[{}|{}]", execution.unit_name, execution.operation.to_str());
+
+    // Only worth mentioning when the unit failed
+    match execution.exit_code {
+        0 => output.push('\n'),
+        code => output.push_str(&format!(" exited with {}\n", code))
+    }
+
+    for line in execution.stdout.lines() {
+        output.push_str(&format!("1> {}\n", line));
+    }
+
+    for line in execution.stderr.lines() {
+        output.push_str(&format!("2> {}\n", line));
+    }
+
+    output
+}
+
+/// Escapes text for use in XML, dropping control characters which XML 1.0
+/// can't represent at all
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c < ' ' => (),
+            c => escaped.push(c)
+        }
+    }
+
+    escaped
+}
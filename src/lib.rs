@@ -0,0 +1,36 @@
+//! Sysunit applies units, small executables which check, apply and roll back
+//! some piece of a system's state, along with everything they depend upon.
+//!
+//! The command line is built upon the [`Builder`] API, which can be used to
+//! embed sysunit and receive structured results rather than printed ones.
+
+pub(crate) mod error;
+pub(crate) mod unit;
+pub(crate) mod execution;
+pub(crate) mod resolver;
+pub(crate) mod engine;
+pub(crate) mod ui;
+pub(crate) mod operation;
+pub(crate) mod meta;
+pub(crate) mod doc;
+pub(crate) mod inventory;
+pub(crate) mod journal;
+pub(crate) mod drift;
+pub(crate) mod events;
+pub(crate) mod junit;
+pub(crate) mod graph;
+mod fs_util;
+mod api;
+
+pub use api::{Builder, Plan, Report, InstanceReport};
+pub use doc::Format as DocFormat;
+pub use drift::Entry as DriftEntry;
+pub use engine::{Summary, FleetSummary, HostSummary, DEFAULT_TIMEOUT};
+pub use error::Error;
+pub use events::EventSink;
+pub use execution::{Execution, OutputLine, Stream};
+pub use graph::Format as GraphFormat;
+pub use journal::Entry as JournalEntry;
+pub use operation::Operation;
+pub use ui::{hosts_table, JsonReporter, Mode};
+pub use unit::{ApplicationState, Argument, ArgSet, InstanceId, Outcome, RetryPolicy};
@@ -1,17 +1,12 @@
+use std::fs;
 use std::process::exit;
-use clap::{App, Arg};
+use std::time::Duration;
+use clap::{App, Arg, ArgMatches};
 
-mod error;
-mod unit;
-mod execution;
-mod resolver;
-mod engine;
-mod ui;
-mod fs_util;
-mod operation;
-
-use engine::run;
-use ui::Mode;
+use sysunit::{
+    Builder, DocFormat, Error, EventSink, GraphFormat, JsonReporter, Mode, Operation, RetryPolicy,
+    DEFAULT_TIMEOUT, hosts_table
+};
 
 fn main() {
     env_logger::init();
@@ -19,21 +14,64 @@ fn main() {
     let matches = App::new("Sysunit")
         .version("0.1")
         .arg(Arg::with_name("operation").required(true))
-        .arg(Arg::with_name("unit").required(true))
-        .arg(Arg::with_name("params").required(false))
+        .arg(Arg::with_name("units")
+            .required(false)
+            .multiple(true)
+            .value_name("UNIT[:ARGS]")
+            .help("Units to operate upon, e.g. `nginx:port=80 certbot:domain=x`, \
+                preceded by a group or host when using an inventory")
+        )
+        .arg(Arg::with_name("inventory")
+            .short("i")
+            .long("inventory")
+            .value_name("FILE")
+            .help("Reads targets from a TOML inventory of hosts and groups")
+            .takes_value(true)
+            .conflicts_with_all(&["target", "targets-file"])
+            .required(false)
+        )
         .arg(Arg::with_name("reporting-mode")
             .short("r")
             .long("reporting-mode")
             .value_name("MODE")
             .help("Sets format of reporting")
             .takes_value(true)
-            .possible_values(&["min", "full"])
+            .possible_values(&["min", "full", "json"])
+        )
+        .arg(Arg::with_name("format")
+            .short("f")
+            .long("format")
+            .value_name("FORMAT")
+            .help("Sets the format of documentation produced by doc, or of the graph produced by graph")
+            .takes_value(true)
+            .possible_values(&["terminal", "markdown", "dot", "mermaid", "json"])
+        )
+        .arg(Arg::with_name("check-state")
+            .long("check-state")
+            .help("Checks each unit in the graph and colours it by the result")
         )
         .arg(Arg::with_name("target")
             .short("t")
             .long("target")
             .value_name("TARGET_URL")
-            .help("Specifies the target against which the unit operation will be executed")
+            .help("Specifies a target against which the unit operation will be executed, \
+                may be given several times")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .required(false)
+        )
+        .arg(Arg::with_name("targets-file")
+            .long("targets-file")
+            .value_name("FILE")
+            .help("Reads target URLs from a file, one per line")
+            .takes_value(true)
+            .required(false)
+        )
+        .arg(Arg::with_name("parallel-hosts")
+            .long("parallel-hosts")
+            .value_name("N")
+            .help("Number of targets to operate upon at once")
             .takes_value(true)
             .required(false)
         )
@@ -45,28 +83,305 @@ fn main() {
             .takes_value(true)
             .required(false)
         )
+        .arg(Arg::with_name("root-only")
+            .long("root-only")
+            .help("Only rolls back the requested unit, leaving its dependencies in place")
+        )
+        .arg(Arg::with_name("rollback")
+            .long("rollback")
+            .help("Makes plan describe a rollback rather than an apply")
+        )
+        .arg(Arg::with_name("fail-fast")
+            .long("fail-fast")
+            .help("Stops executing all units after the first failure")
+        )
+        .arg(Arg::with_name("atomic")
+            .long("atomic")
+            .help("Rolls back every unit applied during the run if any unit fails")
+        )
+        .arg(Arg::with_name("jobs")
+            .short("j")
+            .long("jobs")
+            .value_name("N")
+            .help("Number of independent units to execute at once")
+            .takes_value(true)
+            .required(false)
+        )
+        .arg(Arg::with_name("timeout")
+            .long("timeout")
+            .value_name("SECONDS")
+            .help("Stops units which run for longer, unless they give a timeout of their own. \
+                Defaults to 1800, 0 disables")
+            .takes_value(true)
+            .required(false)
+        )
+        .arg(Arg::with_name("retries")
+            .long("retries")
+            .value_name("N")
+            .help("Attempts a failed apply up to N more times, unless the unit gives a \
+                retry policy of its own")
+            .takes_value(true)
+            .required(false)
+        )
+        .arg(Arg::with_name("retry-backoff")
+            .long("retry-backoff")
+            .value_name("SECONDS")
+            .help("Wait before the first retry, which doubles for each retry after it")
+            .takes_value(true)
+            .required(false)
+        )
+        .arg(Arg::with_name("retry-exit-codes")
+            .long("retry-exit-codes")
+            .value_name("CODES")
+            .help("Comma separated exit codes worth retrying.  When given, timeouts and \
+                units which don't converge aren't retried.  Any failure is by default.")
+            .takes_value(true)
+            .required(false)
+        )
+        .arg(Arg::with_name("report-file")
+            .long("report-file")
+            .value_name("PATH")
+            .help("Also writes a JUnit XML report of the run to PATH")
+            .takes_value(true)
+            .required(false)
+        )
+        .arg(Arg::with_name("journal")
+            .long("journal")
+            .value_name("PATH")
+            .help("Records what was applied in a journal at PATH on each target")
+            .takes_value(true)
+            .required(false)
+        )
         .get_matches();
 
-    let unit_name = matches.value_of("unit").unwrap();
-    let operation = matches.value_of("operation").unwrap();
-    let arg_str = matches.value_of("params").unwrap_or("");
-    let adapter_name = matches.value_of("adapter");
-    let target_url_str = matches.value_of("target");
-
     let reporting_mode_value = 
         matches.value_of("reporting-mode").unwrap_or("min");
 
     let reporting_mode = match reporting_mode_value {
         "min" => Mode::Minimal,
         "full" => Mode::Full,
+        "json" => Mode::Json,
         _ => panic!("Impossible reporting-mode: {}", reporting_mode_value)
     };
 
-    match run(unit_name, operation, arg_str, target_url_str, adapter_name, reporting_mode) {
-        Ok(_) => exit(0),
+    let operation_name = matches.value_of("operation").unwrap();
+    let operation = operation_name.parse::<Operation>().unwrap_or_else(|e| fail(&e, reporting_mode));
+    let mut unit_values: Vec<&str> = matches.values_of("units").map_or(Vec::new(), |v| v.collect());
+    // history may be given a unit to limit itself to, everything else needs one
+    let needs_units = !matches!(operation, Operation::History);
+
+    if unit_values.is_empty() && (needs_units || matches.is_present("inventory")) {
+        println!("No units given to {}", operation_name);
+        exit(1)
+    }
+
+    let mut builder = Builder::new(operation).reporting_mode(reporting_mode);
+
+    if let Some(adapter) = matches.value_of("adapter") {
+        builder = builder.adapter(adapter);
+    }
+
+    match matches.value_of("inventory") {
+        Some(path) => {
+            let selection = unit_values.remove(0);
+
+            if unit_values.is_empty() && needs_units {
+                println!("No units given to run against `{}`", selection);
+                exit(1)
+            }
+
+            builder = builder.inventory(path, selection);
+        },
+        None => for url in target_urls(&matches) {
+            builder = builder.target(&url);
+        }
+    }
+
+    let root_specs = match join_legacy_params(unit_values) {
+        Ok(specs) => specs,
         Err(e) => {
             println!("{}", e.msg);
             exit(1)
-        },
+        }
+    };
+
+    for spec in root_specs.iter() {
+        builder = builder.unit(spec);
     }
+
+    let default_timeout = DEFAULT_TIMEOUT.as_secs().to_string();
+    let timeout = match matches.value_of("timeout").unwrap_or(&default_timeout).parse::<u64>() {
+        Ok(0) => None,
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => {
+            println!("--timeout must be a number of seconds");
+            exit(1)
+        }
+    };
+
+    let retry = match retry_policy(&matches) {
+        Ok(policy) => policy,
+        Err(e) => {
+            println!("{}", e.msg);
+            exit(1)
+        }
+    };
+
+    let format_value = matches.value_of("format");
+
+    let doc_format = match format_value.filter(|_| matches!(operation, Operation::Doc)).unwrap_or("terminal") {
+        "terminal" => DocFormat::Terminal,
+        "markdown" => DocFormat::Markdown,
+        other => {
+            println!("doc can not be rendered as {}", other);
+            exit(1)
+        }
+    };
+
+    let graph_format = match format_value.filter(|_| matches!(operation, Operation::Graph)).unwrap_or("dot") {
+        "dot" => GraphFormat::Dot,
+        "mermaid" => GraphFormat::Mermaid,
+        "json" => GraphFormat::Json,
+        other => {
+            println!("graph can not be rendered as {}", other);
+            exit(1)
+        }
+    };
+
+    let builder = builder
+        .root_only(matches.is_present("root-only"))
+        .plan_rollback(matches.is_present("rollback"))
+        .fail_fast(matches.is_present("fail-fast"))
+        .atomic(matches.is_present("atomic"))
+        .jobs(positive_integer(&matches, "jobs"))
+        .parallel_hosts(positive_integer(&matches, "parallel-hosts"))
+        .doc_format(doc_format)
+        .graph_format(graph_format)
+        .check_state(matches.is_present("check-state"))
+        .timeout(timeout)
+        .retry(retry)
+        .journal(matches.value_of("journal").map(|p| p.to_string()))
+        .report_file(matches.value_of("report-file").map(|p| p.to_string()));
+
+    let fleet = builder.run().unwrap_or_else(|e| fail(&e, reporting_mode));
+
+    // Each host's progress was prefixed with its name, so a table sums them
+    // up.  JSON gives each host's summary already.  Drift's report is TOML
+    // meant to be parsed, so the table goes to stderr out of its way.
+    if fleet.hosts.len() > 1 {
+        match (operation, reporting_mode) {
+            (_, Mode::Json) => (),
+            (Operation::Drift, _) => eprint!("{}", hosts_table(&fleet)),
+            _ => print!("{}", hosts_table(&fleet))
+        }
+    }
+
+    exit(fleet.exit_code())
+}
+
+/// Reports an error which ended the run and exits
+fn fail(error: &Error, reporting_mode: Mode) -> ! {
+    match reporting_mode {
+        Mode::Json => JsonReporter::new(None).error(error),
+        _ => println!("{}", error.msg)
+    }
+    exit(1)
+}
+
+/// URLs given by `--target` and `--targets-file`
+fn target_urls(matches: &ArgMatches) -> Vec<String> {
+    let mut target_urls: Vec<String> = match matches.values_of("target") {
+        Some(values) => values.map(|v| v.to_string()).collect(),
+        None => Vec::new()
+    };
+
+    if let Some(path) = matches.value_of("targets-file") {
+        match read_targets_file(path) {
+            Ok(urls) => target_urls.extend(urls),
+            Err(e) => {
+                println!("{}", e.msg);
+                exit(1)
+            }
+        }
+    }
+
+    target_urls
+}
+
+/// Reads the value of a numeric option, defaulting to 1
+fn positive_integer(matches: &ArgMatches, name: &str) -> usize {
+    match matches.value_of(name).unwrap_or("1").parse::<usize>() {
+        Ok(n) if n > 0 => n,
+        _ => {
+            println!("--{} must be a positive integer", name);
+            exit(1)
+        }
+    }
+}
+
+fn retry_policy(matches: &ArgMatches) -> Result<RetryPolicy, Error> {
+    let count = matches.value_of("retries").unwrap_or("0").parse::<u32>().map_err(|_|
+        Error::new("--retries must be a number".to_string())
+    )?;
+
+    let mut policy = RetryPolicy::new(count);
+
+    if let Some(value) = matches.value_of("retry-backoff") {
+        let seconds = value.parse::<u64>().map_err(|_|
+            Error::new("--retry-backoff must be a number of seconds".to_string())
+        )?;
+        policy.backoff = Duration::from_secs(seconds);
+    }
+
+    if let Some(value) = matches.value_of("retry-exit-codes") {
+        policy.exit_codes = value.split(',').map(|code|
+            code.trim().parse::<i32>().map_err(|_|
+                Error::new(format!("Invalid exit code in --retry-exit-codes: {}", code))
+            )
+        ).collect::<Result<Vec<i32>, Error>>()?;
+    }
+
+    Ok(policy)
+}
+
+/// Reads target URLs from a file, skipping blank lines and `#` comments
+fn read_targets_file(path: &str) -> Result<Vec<String>, Error> {
+    let contents = fs::read_to_string(path).map_err(|e|
+        Error::new(format!("Could not read targets file {}: {}", path, e))
+    )?;
+
+    let urls = contents.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_string())
+        .collect();
+
+    Ok(urls)
+}
+
+/// Supports the original `sysunit apply unit key=value` form by attaching a
+/// bare `key=value` argument to the unit preceding it.  A unit which was
+/// already given arguments after a colon can't take more this way.
+fn join_legacy_params(values: Vec<&str>) -> Result<Vec<String>, Error> {
+    let mut root_specs: Vec<String> = Vec::new();
+
+    for value in values {
+        let is_params = value.contains('=') && !value.contains(':');
+
+        match root_specs.last_mut() {
+            Some(previous) if is_params && previous.contains(':') => {
+                return Err(Error::new(format!(
+                    "`{}` already has arguments, so `{}` must be given after its colon, e.g. `{},{}`",
+                    previous, value, previous, value
+                )))
+            },
+            Some(previous) if is_params => {
+                previous.push(':');
+                previous.push_str(value);
+            },
+            _ => root_specs.push(value.to_string())
+        }
+    }
+
+    Ok(root_specs)
 }
@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Deserialize;
+use toml::Value;
+
+use crate::error::Error;
+use crate::execution::Target;
+use crate::operation::Operation;
+use crate::unit::{Instance, DefinitionType, Timeouts, RetryPolicy};
+
+/// Structured documentation for a unit, given as TOML either by the unit's
+/// `meta` operation, a `unit.toml` file within a directory unit, or the
+/// top-level keys of a manifest:
+///
+/// ```toml
+/// description = "Installs a package"
+/// operations = ["check", "apply", "rollback"]
+/// timeout = 60
+///
+/// [timeouts]
+/// apply = 600
+///
+/// [retry]
+/// count = 3
+/// backoff = 10
+/// exit_codes = [100]
+///
+/// [[arg]]
+/// name = "name"
+/// type = "string"
+/// help = "Name of the package to install"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+pub struct Meta {
+    pub description: Option<String>,
+    #[serde(default)]
+    pub operations: Vec<String>,
+    #[serde(default, rename = "arg")]
+    pub args: Vec<ArgMeta>,
+    /// Seconds any of the unit's operations may run for
+    pub timeout: Option<u64>,
+    /// Seconds particular operations may run for, overriding `timeout`
+    #[serde(default)]
+    pub timeouts: HashMap<String, u64>,
+    pub retry: Option<RetryMeta>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RetryMeta {
+    pub count: u32,
+    /// Seconds to wait before the first retry
+    pub backoff: Option<u64>,
+    #[serde(default)]
+    pub exit_codes: Vec<i32>,
+}
+
+impl RetryMeta {
+    pub fn policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            count: self.count,
+            backoff: Duration::from_secs(self.backoff.unwrap_or(DEFAULT_RETRY_BACKOFF)),
+            exit_codes: self.exit_codes.clone(),
+        }
+    }
+}
+
+/// Seconds to wait before the first retry unless told otherwise
+pub const DEFAULT_RETRY_BACKOFF: u64 = 5;
+
+impl Meta {
+    pub fn timeouts(&self) -> Timeouts {
+        Timeouts {
+            all: self.timeout.map(Duration::from_secs),
+            operations: self.timeouts.iter()
+                .map(|(operation, seconds)| (operation.clone(), Duration::from_secs(*seconds)))
+                .collect()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ArgMeta {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub arg_type: Option<String>,
+    pub default: Option<Value>,
+    pub help: Option<String>,
+}
+
+pub fn parse(document: &str) -> Result<Meta, Error> {
+    toml::from_str(document).map_err(|e| wrap_error!("Unit metadata parse error: {}", e))
+}
+
+/// Gathers metadata for an instance's unit.  Files on the local machine are
+/// preferred, and the unit's `meta` operation is executed on the target
+/// otherwise.
+pub fn load(target: &mut Target, instance: &Instance) -> Result<Meta, Error> {
+    let definition = &instance.definition;
+
+    match definition.definition_type {
+        DefinitionType::Manifest => read(Path::new(&definition.path)),
+        DefinitionType::Directory => {
+            let meta_path = Path::new(&definition.path).join("unit.toml");
+
+            match meta_path.is_file() {
+                true => read(&meta_path),
+                false => execute(target, instance)
+            }
+        },
+        DefinitionType::Executable => execute(target, instance)
+    }
+}
+
+pub fn read(path: &Path) -> Result<Meta, Error> {
+    let document = fs::read_to_string(path).map_err(|e|
+        Error::new(format!("Could not read {}: {}", path.to_string_lossy(), e))
+    )?;
+
+    parse(&document)
+}
+
+fn execute(target: &mut Target, instance: &Instance) -> Result<Meta, Error> {
+    let execution = target.execute(&instance.job(), Operation::Meta)?;
+
+    if !execution.success() {
+        return Err(Error::new(format!("Unit {} meta exited with {}: {}",
+            execution.unit_name,
+            execution.exit_code,
+            execution.stderr
+        )))
+    }
+
+    parse(&execution.stdout)
+}
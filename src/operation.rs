@@ -1,3 +1,5 @@
+use std::str::FromStr;
+
 use crate::error::Error;
 
 #[derive(Clone, Copy, Debug)]
@@ -5,29 +7,54 @@ pub enum Operation {
     Check,
     Apply,
     Rollback,
-    Deps
+    Deps,
+    Meta,
+    Test,
+    Plan,
+    Doc,
+    History,
+    Drift,
+    Graph
 }
 
-impl Operation {
-    pub fn from_str(operation_name: &str) -> Result<Operation, Error> {
+impl FromStr for Operation {
+    type Err = Error;
+
+    fn from_str(operation_name: &str) -> Result<Operation, Error> {
         match operation_name {
             "check" => Ok(Operation::Check),
             "apply" => Ok(Operation::Apply),
             "rollback" => Ok(Operation::Rollback),
             "deps" => Ok(Operation::Deps),
+            "meta" => Ok(Operation::Meta),
+            "test" => Ok(Operation::Test),
+            "plan" => Ok(Operation::Plan),
+            "doc" => Ok(Operation::Doc),
+            "history" => Ok(Operation::History),
+            "drift" => Ok(Operation::Drift),
+            "graph" => Ok(Operation::Graph),
             _ => {
                 let err_string = format!("Unkown operation {}", operation_name);
                 Err(Error::new(err_string))
             }
         }
     }
+}
 
+impl Operation {
     pub fn to_str(&self) -> &'static str{
         match self {
             Operation::Check => "check",
             Operation::Apply => "apply",
             Operation::Rollback => "rollback",
             Operation::Deps => "deps",
+            Operation::Meta => "meta",
+            Operation::Test => "test",
+            Operation::Plan => "plan",
+            Operation::Doc => "doc",
+            Operation::History => "history",
+            Operation::Drift => "drift",
+            Operation::Graph => "graph",
         }
     }
 }
@@ -1,54 +1,93 @@
 use std::rc::Rc;
+use std::sync::Arc;
 use std::cell::RefCell;
 
-use crate::unit::{Instance, RunState, ArgSet, InstanceId};
+use crate::unit::{Instance, RunState, InstanceId, DefinitionType, ArgSet};
 use crate::error::Error;
 use crate::execution::Target;
 use crate::operation::Operation;
+use crate::events::EventSink;
+use crate::api::reports;
 
 mod instance_cache;
 mod loader;
+mod manifest;
 
 use self::instance_cache::InstanceCache;
+pub use self::loader::{load_unit, default_unit_paths};
+pub use self::instance_cache::InstanceRc;
 
 pub type InstanceVec = Vec<Rc<RefCell<Instance>>>;
 
+/// The graph of instances needed to reach the requested root units' state
+pub struct Resolution {
+    /// Every instance, ordered so that dependencies precede their dependents
+    pub ordered_instances: InstanceVec,
+    /// Instances for the units requested on the command line
+    pub roots: InstanceVec,
+}
+
+/// Resolves several root units, given in `name[:key=value,...]` form, into a
+/// single graph so that dependencies they share are only resolved once.
+/// `default_args` are given to each root unit unless its spec sets them.
 pub fn resolve(
     target: &mut Target,
-    unit_name: &str,
-    args_str: &str
-) -> Result<InstanceVec, Error> {
-    let mut resolver = Resolver::new(target);
-    resolver.resolve(unit_name, args_str)?;
+    root_specs: &[&str],
+    default_args: &ArgSet,
+    unit_paths: &[String],
+    events: &dyn EventSink,
+) -> Result<Resolution, Error> {
+    events.resolution_started(root_specs);
+
+    let mut resolver = Resolver::new(target, unit_paths, events);
+
+    for root_spec in root_specs.iter() {
+        let mut instance_id = InstanceId::parse(root_spec)?;
+        instance_id.args.merge_defaults(default_args);
+
+        resolver.resolve(instance_id)?;
+    }
 
-    Ok(resolver.ordered_instances)
+    events.resolution_finished(&reports(&resolver.ordered_instances));
+
+    Ok(Resolution {
+        ordered_instances: resolver.ordered_instances,
+        roots: resolver.roots,
+    })
 }
 
 struct Resolver <'a> {
     pub ordered_instances: InstanceVec,
+    pub roots: InstanceVec,
     instance_cache: InstanceCache,
     target: &'a mut Target,
+    events: &'a dyn EventSink,
+    /// Instances being resolved, from the root down to the current one
+    stack: Vec<InstanceId>,
 }
 
 impl <'a> Resolver <'a> {
-    pub fn new(target: &'a mut Target) -> Resolver<'a> {
-        let instance_cache = InstanceCache::new();
+    pub fn new(target: &'a mut Target, unit_paths: &[String], events: &'a dyn EventSink) -> Resolver<'a> {
+        let instance_cache = InstanceCache::new(unit_paths);
 
         Resolver {
-            target: target,
-            instance_cache: instance_cache,
+            target,
+            events,
+            stack: Vec::new(),
+            instance_cache,
             ordered_instances: Vec::new(),
+            roots: Vec::new(),
         }
     }
 
-    pub fn resolve(&mut self, unit_name: &str, args_str: &str) -> Result<(), Error> {
-        let instance_id = InstanceId::build(unit_name, args_str)?;
+    pub fn resolve(&mut self, instance_id: InstanceId) -> Result<(), Error> {
         let instance = self.instance_cache.get(instance_id)?;
 
-        self.ordered_instances = Vec::new();
-        self.visit(instance)?;
+        if !self.roots.iter().any(|root| Rc::ptr_eq(root, &instance)) {
+            self.roots.push(Rc::clone(&instance));
+        }
 
-        Ok(())
+        self.visit(instance)
     }
 
     fn visit(&mut self, instance_rc: Rc<RefCell<Instance>>) -> Result<(), Error>{
@@ -58,30 +97,70 @@ impl <'a> Resolver <'a> {
 
         match run_state {
             RunState::Init => {
-                set_state(&instance_refcell, RunState::Resolving);
-                for child in self.get_deps(&instance_refcell.borrow())?.iter() {
-                    let clone = Rc::clone(&child);
+                set_state(instance_refcell, RunState::Resolving);
+                self.stack.push(instance_refcell.borrow().id.clone());
+                let children = self.get_deps(&instance_refcell.borrow())?;
+                let child_ids = children.iter()
+                    .map(|child| child.borrow().id.clone())
+                    .collect::<Vec<InstanceId>>();
+                self.events.deps_discovered(&instance_refcell.borrow().id, &child_ids);
+
+                for child in children.iter() {
+                    let clone = Rc::clone(child);
                     self.visit(clone)?;
                 }
-                set_state(&instance_refcell, RunState::Resolved);
+                instance_refcell.borrow_mut().dependencies = children.iter()
+                    .map(|child| child.borrow().id.signature())
+                    .collect();
+                set_state(instance_refcell, RunState::Resolved);
+                self.stack.pop();
                 self.ordered_instances.push(Rc::clone(&instance_clone));
-                return Ok(())
+                Ok(())
             },
             RunState::Resolving => {
-                let definition = Rc::clone(&instance_refcell.borrow().definition_rc);
+                let instance_id = instance_refcell.borrow().id.clone();
 
-                let error = Error::new(
-                    format!("Circular dependency on {}", definition.name)
-                ); 
-                return Err(error)
+                Err(self.cycle_error(&instance_id))
             },
             RunState::Resolved => Ok(()),
         }
     }
 
+    /// Describes the cycle which leads from the instance back to itself, and
+    /// the root unit it was reached from
+    fn cycle_error(&self, instance_id: &InstanceId) -> Error {
+        let signature = instance_id.signature();
+        let start = self.stack.iter()
+            .position(|id| id.signature() == signature)
+            .unwrap_or(0);
+
+        let cycle = self.stack[start..].iter()
+            .chain(std::iter::once(instance_id))
+            .map(|id| id.to_string())
+            .collect::<Vec<String>>()
+            .join(" -> ");
+
+        let root = self.stack.first().unwrap_or(instance_id);
+
+        Error::new(format!("Circular dependency: {}, reached from root unit {}", cycle, root))
+    }
+
     fn get_deps(&mut self, instance: &Instance) -> Result<Vec<Rc<RefCell<Instance>>>, Error> {
-        let execution_result = self.target.execute(instance, Operation::Deps)?;
-        let definition = Rc::clone(&instance.definition_rc);
+        if let DefinitionType::Manifest = instance.definition.definition_type {
+            return manifest::load_deps(&instance.definition.path)?.into_iter()
+                .map(|id| self.instance_cache.get(id))
+                .collect()
+        }
+
+        let execution_result = self.target.execute(&instance.job(), Operation::Deps)?;
+        let definition = Arc::clone(&instance.definition);
+
+        if let Some(timeout) = execution_result.timed_out {
+            return Err(Error::new(format!("Unit {} deps timed out after {}s",
+                definition.name,
+                timeout.as_secs()
+            )));
+        }
 
         if execution_result.exit_code != 0 {
             let error = Error::new(format!("Unit {} deps exited with {}: {}",
@@ -93,37 +172,37 @@ impl <'a> Resolver <'a> {
             return Err(error);
         }
 
-        execution_result.stdout.lines()
-            .filter(|line| !line.is_empty())
-            .map(|line| parse_dependency(instance, line))
-            .map(|instance_id| {
-                match instance_id {
-                    Ok(id) => self.instance_cache.get(id),
-                    Err(e) => Err(e),
-                }
-            })
+        parse_deps_output(instance, &execution_result.stdout)?.into_iter()
+            .map(|id| self.instance_cache.get(id))
             .collect::<Result<Vec<Rc<RefCell<Instance>>>, Error>>()
     }
 }
 
-fn parse_dependency(instance: &Instance, line: &str) -> Result<InstanceId, Error> {
-    let parts = line.split(":").collect::<Vec<&str>>();
-    match parts.len() {
-        1 => Ok(InstanceId::new(line.to_string(), ArgSet::new())),
-        2 =>  Ok(InstanceId::build(parts[0], parts[1])?),
-        _ => {
-            let definition_rc = Rc::clone(&instance.definition_rc);
-            let msg = format!(
-                "Unit {} deps parse error on string: {}",
-                definition_rc.name,
-                line.to_string()
-            );
-
-            let error = Error::new(msg);
-
-            Err(error)
-        }
+/// Units may print their dependencies as a TOML document of `dep` tables, in
+/// the same form as manifests use, which allows for arguments of any type.
+/// Output which isn't such a document is read as one `name` or
+/// `name:key=value,key=value` dependency per line.
+fn parse_deps_output(instance: &Instance, output: &str) -> Result<Vec<InstanceId>, Error> {
+    if let Some(document) = manifest::parse_deps_document(output) {
+        return document.map_err(|e| {
+            let definition = Arc::clone(&instance.definition);
+
+            Error::new(format!("Unit {} deps parse error: {}", definition.name, e.msg))
+        })
     }
+
+    output.lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| parse_dependency(instance, line))
+        .collect()
+}
+
+fn parse_dependency(instance: &Instance, line: &str) -> Result<InstanceId, Error> {
+    InstanceId::parse(line).map_err(|e| {
+        let definition = Arc::clone(&instance.definition);
+
+        Error::new(format!("Unit {} deps parse error: {}", definition.name, e.msg))
+    })
 }
 
 fn set_state(instance_refcell: &RefCell<Instance>, run_state: RunState) {
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
-use crate::unit::{Instance, DefinitionRc, InstanceId};
+use crate::unit::{Instance, DefinitionRef, InstanceId};
 use std::rc::Rc;
+use std::sync::Arc;
 use std::cell::RefCell;
 use crate::error::Error;
 
@@ -8,7 +9,7 @@ use super::loader::load_unit;
 
 pub type InstanceRc = Rc<RefCell<Instance>>;
 
-type DefinitionRcResult = Result<DefinitionRc, Error>;
+type DefinitionRefResult = Result<DefinitionRef, Error>;
 
 pub struct InstanceCache {
     lookup_table: HashMap<String, InstanceRc>,
@@ -16,14 +17,15 @@ pub struct InstanceCache {
 }
 
 struct DefinitionCache {
-    lookup_table: HashMap<String, DefinitionRc>
+    lookup_table: HashMap<String, DefinitionRef>,
+    unit_paths: Vec<String>,
 }
 
 impl InstanceCache {
-    pub fn new() -> InstanceCache {
+    pub fn new(unit_paths: &[String]) -> InstanceCache {
         InstanceCache {
             lookup_table: HashMap::new(),
-            definition_cache: DefinitionCache::new(),
+            definition_cache: DefinitionCache::new(unit_paths),
         }
     }
 
@@ -32,34 +34,34 @@ impl InstanceCache {
         match self.lookup_table.get(&signature) {
             Some(instance) => Ok(Rc::clone(instance)),
             None => {
-                let definition_rc = self.definition_cache.get(&instance_id.name)?;
-                let instance = Instance::new(definition_rc, instance_id);
+                let definition = self.definition_cache.get(&instance_id.name)?;
+                let instance = Instance::new(definition, instance_id);
                 let cell = RefCell::new(instance);
                 let rc = Rc::new(cell);
                 let rc_clone = Rc::clone(&rc);
 
                 self.lookup_table.insert(signature, rc);
 
-                return Ok(rc_clone)
+                Ok(rc_clone)
             }
         }
     }
 }
 
 impl DefinitionCache {
-    pub fn new() -> DefinitionCache {
-        DefinitionCache { lookup_table: HashMap::new() }
+    pub fn new(unit_paths: &[String]) -> DefinitionCache {
+        DefinitionCache { lookup_table: HashMap::new(), unit_paths: unit_paths.to_vec() }
     }
 
-    pub fn get(&mut self, unit_name: &str) -> DefinitionRcResult {
+    pub fn get(&mut self, unit_name: &str) -> DefinitionRefResult {
         let unit_name_string = unit_name.to_string();
 
         match self.lookup_table.get(&unit_name_string) {
-            Some(definition) => Ok(Rc::clone(definition)),
+            Some(definition) => Ok(Arc::clone(definition)),
             None => {
-                let definition = load_unit(unit_name)?;
-                let rc = Rc::new(definition);
-                let rc_clone = Rc::clone(&rc);
+                let definition = load_unit(unit_name, &self.unit_paths)?;
+                let rc = Arc::new(definition);
+                let rc_clone = Arc::clone(&rc);
 
                 self.lookup_table.insert(unit_name_string, rc);
 
@@ -4,23 +4,55 @@ use std::path::Path;
 use crate::unit::{Definition, DefinitionType};
 use crate::error::Error;
 use crate::fs_util;
+use crate::meta;
 
-const DEFAULT_DIRS: &'static [&'static str] = &["./units", "/etc/units"];
+const DEFAULT_DIRS: &[&str] = &["./units", "/etc/units"];
 
-pub fn load_unit(name: &str) -> Result<Definition, Error> {
-    let directories = fs_util::get_path_var("SYSUNIT_PATH", DEFAULT_DIRS);
+/// Directories units are looked for in: those in SYSUNIT_PATH, or the
+/// defaults if it isn't set
+pub fn default_unit_paths() -> Vec<String> {
+    fs_util::get_path_var("SYSUNIT_PATH", DEFAULT_DIRS)
+}
 
+pub fn load_unit(name: &str, directories: &[String]) -> Result<Definition, Error> {
     for dir in directories.iter() {
         let dir_path = Path::new(dir);
-        let full_path = dir_path.join(&name);
+        let full_path = dir_path.join(name);
+
+        let found = match unit_type(&full_path)? {
+            Some(definition_type) => Some((full_path, definition_type)),
+            None => {
+                let manifest_path = dir_path.join(format!("{}.toml", name));
+
+                match manifest_path.is_file() {
+                    true => Some((manifest_path, DefinitionType::Manifest)),
+                    false => None
+                }
+            }
+        };
 
-        match unit_type(&full_path)? {
+        match found {
             None => continue,
-            Some(definition_type) => {
+            Some((full_path, definition_type)) => {
+                let has_test = match definition_type {
+                    DefinitionType::Directory => is_executable(&full_path.join("test")),
+                    _ => false
+                };
+                // Only directory units have metadata we can read without
+                // executing them, which is where their timeouts and retry
+                // policies are given
+                let meta_path = full_path.join("unit.toml");
+                let meta = match definition_type {
+                    DefinitionType::Directory if meta_path.is_file() => meta::read(&meta_path)?,
+                    _ => Default::default()
+                };
                 let full_path_os_string = full_path.into_os_string();
                 let full_path_str = full_path_os_string.to_str()
                     .expect("Invalid unit path UTF8 string!");
-                let unit = Definition::new(name, full_path_str, definition_type);
+                let mut unit = Definition::new(name, full_path_str, definition_type);
+                unit.has_test = has_test;
+                unit.timeouts = meta.timeouts();
+                unit.retry = meta.retry.map(|retry| retry.policy());
 
                 return Ok(unit);
             }
@@ -32,7 +64,7 @@ pub fn load_unit(name: &str) -> Result<Definition, Error> {
         directories
     ));
 
-    return Err(error)
+    Err(error)
 }
 
 fn unit_type(full_path: &Path) -> Result<Option<DefinitionType>, Error> {
@@ -63,5 +95,12 @@ fn unit_type(full_path: &Path) -> Result<Option<DefinitionType>, Error> {
         }
     }
 
-    return Ok(None);
+    Ok(None)
+}
+
+fn is_executable(path: &Path) -> bool {
+    match fs::metadata(path) {
+        Ok(metadata) => fs_util::unix::is_executable_file(&metadata),
+        Err(_) => false
+    }
 }
@@ -0,0 +1,66 @@
+use std::fs;
+
+use toml::Value;
+
+use crate::unit::{ArgSet, InstanceId};
+use crate::error::Error;
+
+/// Reads the dependencies listed by a manifest unit's .toml file, which are
+/// given as an array of `dep` tables:
+///
+/// ```toml
+/// [[dep]]
+/// unit = "pkg"
+/// args = { name = "curl" }
+/// ```
+pub fn load_deps(path: &str) -> Result<Vec<InstanceId>, Error> {
+    let document = fs::read_to_string(path).map_err(|e|
+        Error::new(format!("Could not read manifest {}: {}", path, e))
+    )?;
+
+    parse_deps(&document).map_err(|e|
+        Error::new(format!("Manifest {} is invalid: {}", path, e.msg))
+    )
+}
+
+pub fn parse_deps(document: &str) -> Result<Vec<InstanceId>, Error> {
+    let value = document.parse::<Value>().map_err(|e|
+        wrap_error!("TOML parse error: {}", e)
+    )?;
+
+    deps_from_value(&value)
+}
+
+/// Parses the output of a unit's deps operation if it is a TOML document
+/// listing `dep` tables, returning `None` for any other output.
+pub fn parse_deps_document(output: &str) -> Option<Result<Vec<InstanceId>, Error>> {
+    match output.parse::<Value>() {
+        Ok(ref value) if value.get("dep").is_some() => Some(deps_from_value(value)),
+        _ => None
+    }
+}
+
+fn deps_from_value(value: &Value) -> Result<Vec<InstanceId>, Error> {
+    match value.get("dep") {
+        None => Ok(Vec::new()),
+        Some(Value::Array(deps)) => deps.iter().map(parse_dep).collect(),
+        Some(_) => Err(Error::new("`dep` must be an array of tables".to_string()))
+    }
+}
+
+fn parse_dep(dep: &Value) -> Result<InstanceId, Error> {
+    let unit_name = match dep.get("unit") {
+        Some(Value::String(name)) => name,
+        _ => return Err(Error::new(format!("dep {} must give a `unit` name", dep)))
+    };
+
+    let args = match dep.get("args") {
+        None => ArgSet::new(),
+        Some(Value::Table(table)) => ArgSet::from_table(table),
+        Some(_) => return Err(Error::new(format!(
+            "args of dep `{}` must be a table", unit_name
+        )))
+    };
+
+    Ok(InstanceId::new(unit_name.to_string(), args))
+}
@@ -1,39 +1,362 @@
-use crate::execution::Execution;
+use crate::execution::{Execution, OutputLine, Stream};
 use crate::operation::Operation;
 use crate::error::Error;
+use crate::api::InstanceReport;
+use crate::unit::{ApplicationState, Outcome, InstanceId, ArgSet};
+use crate::journal::Entry;
+use crate::drift::{self, Entry as DriftEntry};
+use crate::engine::{Summary, FleetSummary, EXIT_DRIFTED, EXIT_NOT_CONVERGED};
+use crate::events::EventSink;
 
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
 use colored::*;
 use unicode_truncate::UnicodeTruncateStr;
 use rpassword::read_password_from_tty;
 
+pub(crate) mod json;
+
+pub use self::json::JsonReporter;
+
 #[derive(Clone, Copy)]
 pub enum Mode {
     Full,
-    Minimal
-}
-
-pub fn report_execution(execution: &Execution, mode: Mode, operation: Operation) {
-    let (override_mode, unit_name_colored) = if execution.success() {
-        (mode, execution.unit_name.green())
-    } else {
-        (Mode::Full, execution.unit_name.red())
-    };
-
-    let output_reporting = match override_mode {
-        Mode::Full => 
-            format!("\n{}\n{}",
-                prefix_lines(&execution.stdout, "1>"),
-                prefix_lines(&execution.stderr, "2>")),
-        Mode::Minimal => {
-            let first_line = match execution.stdout.lines().into_iter().last() {
-                Some(s) => s,
-                None => ""
+    Minimal,
+    /// One JSON object per line, see `JsonReporter`
+    Json
+}
+
+/// Reports progress in the given mode, prefixing it with the host when several
+/// targets are operated upon at once
+pub fn event_sink(mode: Mode, host: Option<String>) -> Arc<dyn EventSink> {
+    match mode {
+        Mode::Json => Arc::new(JsonReporter::new(host)),
+        _ => Arc::new(Reporter::new(mode, host))
+    }
+}
+
+/// Prints the progress of a run against a single target.  When several targets
+/// are operated upon at once, every line is prefixed with the target's host.
+#[derive(Clone)]
+pub struct Reporter {
+    mode: Mode,
+    host: Option<String>,
+}
+
+impl Reporter {
+    pub fn new(mode: Mode, host: Option<String>) -> Reporter {
+        Reporter { mode, host }
+    }
+
+    /// Executions are labelled by their instance, as several instances of a
+    /// unit may be executing at once
+    fn execution(
+        &self,
+        instance: &InstanceReport,
+        execution: &Execution,
+        operation: Operation,
+        outcome: Option<&Outcome>
+    ) {
+        let outcome_reporting = match (outcome, execution.timed_out) {
+            (_, Some(timeout)) =>
+                format!("\n{} {}s", "timed out after".red(), timeout.as_secs()),
+            (Some(Outcome::NotConverged(reason)), None) =>
+                format!("\n{} {}", "did not converge:".red(), reason.trim_end()),
+            // The execution itself finished, but the check which followed it didn't
+            (Some(Outcome::TimedOut(timeout)), None) =>
+                format!("\n{} {}s", "check timed out after".red(), timeout.as_secs()),
+            (Some(Outcome::Failed(exit_code)), None) =>
+                format!("\n{} {}", "exited with".red(), exit_code),
+            _ => String::new()
+        };
+
+        let label = instance.id.to_string();
+        let (override_mode, label_colored) = if execution.success() && outcome_reporting.is_empty() {
+            (self.mode, label.green())
+        } else {
+            (Mode::Full, label.red())
+        };
+
+        let output_reporting = match override_mode {
+            // Already printed as it arrived
+            _ if self.streams(operation) => String::new(),
+            Mode::Minimal => {
+                let first_line = execution.stdout.lines().last().unwrap_or("");
+                format!(" {}", first_line.unicode_truncate(40).0.trim())
+            },
+            _ => format!("\n{}", execution.output.iter()
+                .map(|line| format!("{}{}\n", stream_prefix(line.stream), line.text))
+                .collect::<String>())
+        };
+
+        self.emit(format!("[{}|{}]{}{}",
+            label_colored,
+            operation.to_str(),
+            output_reporting,
+            outcome_reporting))
+    }
+
+    /// Output of applies, rollbacks and tests, which may run for a while, is
+    /// printed as it arrives
+    fn streams(&self, operation: Operation) -> bool {
+        matches!(operation, Operation::Apply | Operation::Rollback | Operation::Test)
+    }
+
+    /// Prints a block of output in one go, so that blocks from targets being
+    /// operated upon concurrently don't interleave
+    fn emit(&self, output: String) {
+        match &self.host {
+            None => println!("{}", output),
+            Some(host) => print!("{}", prefix_lines(&output, &format!("[{}] ", host.cyan())))
+        }
+    }
+}
+
+impl EventSink for Reporter {
+    /// Checks are only worth printing when checking is all the run does, or
+    /// when one timed out.  Drift includes timeouts in its report instead.
+    fn check_finished(&self, instance: &InstanceReport, execution: &Execution, operation: Operation) {
+        match (operation, execution.timed_out) {
+            (Operation::Drift, _) => (),
+            (_, Some(_)) => self.execution(instance, execution, Operation::Check, None),
+            (Operation::Check, None) => self.execution(instance, execution, operation, None),
+            _ => ()
+        }
+    }
+
+    fn output_line(&self, id: &InstanceId, operation: Operation, line: &OutputLine) {
+        if !self.streams(operation) { return }
+
+        self.emit(format!("[{}|{}] {} {}{}",
+            id,
+            operation.to_str(),
+            DateTime::<Local>::from(line.timestamp).format("%H:%M:%S").to_string().dimmed(),
+            stream_prefix(line.stream),
+            line.text))
+    }
+
+    fn execution_finished(&self, instance: &InstanceReport, execution: &Execution, outcome: Option<&Outcome>) {
+        self.execution(instance, execution, execution.operation, outcome)
+    }
+
+    /// Notes a failed attempt which is about to be retried
+    fn execution_retrying(
+        &self,
+        id: &InstanceId,
+        execution: &Execution,
+        attempt: u32,
+        attempts: u32,
+        delay: Duration
+    ) {
+        let reason = match execution.success() {
+            true => "did not converge".to_string(),
+            false => match execution.timed_out {
+                Some(_) => "timed out".to_string(),
+                None => format!("exited with {}", execution.exit_code)
+            }
+        };
+
+        self.emit(format!("[{}|{}] {}",
+            id.to_string().yellow(),
+            execution.operation.to_str(),
+            format!("attempt {} of {} {}, retrying in {}s",
+                attempt, attempts, reason, delay.as_secs()).yellow()))
+    }
+
+    fn execution_skipped(&self, instance: &InstanceReport, operation: Operation) {
+        self.emit(format!("[{}|{}] {}",
+            instance.id.to_string().yellow(),
+            operation.to_str(),
+            "skipped due to an earlier failure".yellow()))
+    }
+
+    fn summary(&self, summary: &Summary, operation: Operation) {
+        // Only counts of what was applied or rolled back are printed as text,
+        // the other operations having printed their own results
+        if let Operation::Check | Operation::Plan | Operation::Graph | Operation::Drift = operation {
+            return
+        }
+
+        let mut counts = format!("{} converged, {} failed, {} did not converge, {} skipped",
+            summary.converged.len(),
+            summary.failed.len(),
+            summary.not_converged.len(),
+            summary.skipped.len());
+
+        if !summary.timed_out.is_empty() {
+            counts.push_str(&format!(", {} timed out", summary.timed_out.len()));
+        }
+
+        if !summary.reverted.is_empty() {
+            counts.push_str(&format!(", {} reverted", summary.reverted.len()));
+        }
+
+        if let Operation::Test = operation {
+            counts.push_str(&format!("; {} tests passed, {} tests failed",
+                summary.tests_passed.len(),
+                summary.tests_failed.len()));
+        }
+
+        let counts_colored = match summary.exit_code() {
+            0 => counts.green(),
+            _ => counts.red()
+        };
+
+        let mut output = format!("{}: {}", operation.to_str(), counts_colored);
+
+        for (label, instances) in [
+            ("failed", &summary.failed),
+            ("did not converge", &summary.not_converged),
+            ("timed out", &summary.timed_out),
+            ("skipped", &summary.skipped),
+            ("reverted", &summary.reverted),
+            ("tests failed", &summary.tests_failed),
+        ].iter() {
+            if instances.is_empty() { continue }
+
+            output.push_str(&format!("\n  {}: {}", label, instances.join(", ")));
+        }
+
+        self.emit(output)
+    }
+
+    fn plan(&self, instances: &[InstanceReport], operation: Operation) {
+        if instances.is_empty() {
+            self.emit(format!("Nothing to {}", operation.to_str()));
+            return
+        }
+
+        let mut output = format!("{} unit(s) to {}:", instances.len(), operation.to_str());
+
+        for instance in instances.iter() {
+            let state = match &instance.application_state {
+                Some(ApplicationState::NotApplied(reason)) if reason.is_empty() =>
+                    "not applied".to_string(),
+                Some(ApplicationState::NotApplied(reason)) => reason.to_string(),
+                Some(ApplicationState::Applied) => "applied".to_string(),
+                None => "unchecked".to_string(),
+            };
+
+            let args = match instance.id.args.vec.is_empty() {
+                true => String::new(),
+                false => format!(" ({})", instance.id.args)
             };
-            format!(" {}", first_line.unicode_truncate(40).0.trim())
+
+            output.push_str(&format!("\n[{}|{}]{} {}",
+                instance.id.name.yellow(),
+                operation.to_str(),
+                args,
+                state.lines().last().unwrap_or("").unicode_truncate(60).0.trim()));
         }
-    };
 
-    println!("[{}|{}]{}", unit_name_colored, operation.to_str(), output_reporting)
+        self.emit(output)
+    }
+
+    fn history(&self, entries: &[Entry]) {
+        if entries.is_empty() {
+            return self.emit("No journal entries".to_string())
+        }
+
+        let lines = entries.iter()
+            .map(|entry| {
+                let id = InstanceId::new(entry.unit.clone(), ArgSet::from_table(&entry.args));
+                let result = match entry.result.as_str() {
+                    "converged" | "reverted" => entry.result.green(),
+                    _ => entry.result.red()
+                };
+
+                format!("{} {} [{}|{}] {} {}",
+                    entry.timestamp,
+                    entry.run_id,
+                    id,
+                    entry.operation,
+                    result,
+                    &entry.content_hash[..entry.content_hash.len().min(8)])
+            })
+            .collect::<Vec<String>>();
+
+        self.emit(lines.join("\n"))
+    }
+
+    /// Prints drift as TOML, unprefixed so that it can be parsed.  Entries
+    /// carry their host instead.
+    fn drift(&self, entries: &[DriftEntry]) {
+        if entries.is_empty() { return }
+
+        let entries = entries.iter()
+            .map(|entry| DriftEntry { host: self.host.clone(), ..entry.clone() })
+            .collect::<Vec<DriftEntry>>();
+
+        match drift::render(&entries) {
+            Ok(rendered) => println!("{}", rendered),
+            Err(e) => self.error(&e)
+        }
+    }
+
+    fn warning(&self, message: &str) {
+        self.emit(format!("{} {}", "warning:".yellow(), message))
+    }
+
+    /// Errors go to stderr, so that they don't end up in reports on stdout
+    fn error(&self, error: &Error) {
+        let message = format!("{} {}", "error:".red(), error.msg);
+
+        match &self.host {
+            None => eprintln!("{}", message),
+            Some(host) => eprint!("{}", prefix_lines(&message, &format!("[{}] ", host.cyan())))
+        }
+    }
+}
+
+/// Renders a table of each host's results, printed once every host has
+/// finished
+pub fn hosts_table(fleet: &FleetSummary) -> String {
+    let width = fleet.hosts.iter().map(|h| h.host.len()).max().unwrap_or(0).max(4);
+    let row = |host: &str, counts: [String; 6], status: String| format!(
+        "{:width$}  {:>9}  {:>6}  {:>13}  {:>9}  {:>7}  {:>12}  {}\n",
+        host, counts[0], counts[1], counts[2], counts[3], counts[4], counts[5], status,
+        width = width);
+
+    let mut table = format!("\n{}", row("host", [
+        "converged", "failed", "not converged", "timed out", "skipped", "tests failed"
+    ].map(String::from), "status".to_string()));
+
+    for host_summary in fleet.hosts.iter() {
+        table.push_str(&match &host_summary.summary {
+            Ok(summary) => {
+                let status = match summary.exit_code() {
+                    0 => "ok".green(),
+                    EXIT_NOT_CONVERGED => "not converged".red(),
+                    EXIT_DRIFTED => "drifted".yellow(),
+                    _ if !summary.failed.is_empty() => "failed".red(),
+                    _ if !summary.timed_out.is_empty() => "timed out".red(),
+                    _ => "tests failed".red()
+                };
+
+                row(&host_summary.host, [
+                    &summary.converged,
+                    &summary.failed,
+                    &summary.not_converged,
+                    &summary.timed_out,
+                    &summary.skipped,
+                    &summary.tests_failed,
+                ].map(|instances| instances.len().to_string()), status.to_string())
+            },
+            Err(e) => row(&host_summary.host, ["-"; 6].map(String::from),
+                format!("{} {}", "error:".red(), e.msg))
+        });
+    }
+
+    table
+}
+
+fn stream_prefix(stream: Stream) -> &'static str {
+    match stream {
+        Stream::Stdout => "1>",
+        Stream::Stderr => "2>"
+    }
 }
 
 pub fn prefix_lines(output: &str, prefix: &str) -> String {
@@ -0,0 +1,283 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+use toml::Value as TomlValue;
+
+use crate::drift::Entry as DriftEntry;
+use crate::engine::Summary;
+use crate::error::Error;
+use crate::events::EventSink;
+use crate::execution::{Execution, OutputLine};
+use crate::journal::Entry;
+use crate::operation::Operation;
+use crate::api::InstanceReport;
+use crate::unit::{ApplicationState, InstanceId, Outcome};
+
+use std::time::{Duration, UNIX_EPOCH};
+
+/// Prints each event as a JSON object on a line of its own, for CI and other
+/// tools to follow runs by.  Every object has a `type`, and a `host` when
+/// several targets are operated upon at once.
+pub struct JsonReporter {
+    host: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    #[serde(flatten)]
+    record: Record<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    host: Option<&'a String>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Record<'a> {
+    Execution {
+        unit: &'a str,
+        args: Map<String, Value>,
+        operation: &'a str,
+        exit_code: i32,
+        stdout: &'a str,
+        stderr: &'a str,
+        /// In seconds
+        duration: f64,
+        timed_out: bool,
+        application_state: Option<&'a str>,
+        outcome: Option<&'a str>,
+    },
+    Output {
+        unit: &'a str,
+        args: Map<String, Value>,
+        operation: &'a str,
+        stream: &'a str,
+        line: &'a str,
+        /// In seconds since the epoch
+        timestamp: f64,
+    },
+    Retry {
+        unit: &'a str,
+        args: Map<String, Value>,
+        operation: &'a str,
+        attempt: u32,
+        attempts: u32,
+        delay: u64,
+    },
+    Skipped {
+        unit: &'a str,
+        args: Map<String, Value>,
+        operation: &'a str,
+    },
+    Plan {
+        operation: &'a str,
+        units: Vec<PlannedUnit<'a>>,
+    },
+    Drift {
+        unit: &'a str,
+        args: Map<String, Value>,
+        state: &'a str,
+        reason: &'a str,
+    },
+    History {
+        unit: &'a str,
+        args: Map<String, Value>,
+        operation: &'a str,
+        result: &'a str,
+        content_hash: &'a str,
+        timestamp: String,
+        run_id: &'a str,
+    },
+    Summary {
+        operation: &'a str,
+        exit_code: i32,
+        #[serde(flatten)]
+        summary: &'a Summary,
+    },
+    Warning {
+        message: &'a str,
+    },
+    Error {
+        message: &'a str,
+    },
+}
+
+#[derive(Serialize)]
+struct PlannedUnit<'a> {
+    unit: String,
+    args: Map<String, Value>,
+    application_state: Option<&'a str>,
+}
+
+impl JsonReporter {
+    pub fn new(host: Option<String>) -> JsonReporter {
+        JsonReporter { host }
+    }
+
+    fn line(&self, record: Record) -> String {
+        let line = Line { record, host: self.host.as_ref() };
+
+        serde_json::to_string(&line).expect("Could not render JSON report")
+    }
+
+    fn emit(&self, record: Record) {
+        println!("{}", self.line(record))
+    }
+
+    fn execution(&self, instance: &InstanceReport, execution: &Execution, outcome: Option<&Outcome>) {
+        self.emit(Record::Execution {
+            unit: &execution.unit_name,
+            args: instance_args(&instance.id),
+            operation: execution.operation.to_str(),
+            exit_code: execution.exit_code,
+            stdout: &execution.stdout,
+            stderr: &execution.stderr,
+            duration: execution.duration.as_secs_f64(),
+            timed_out: execution.timed_out.is_some(),
+            application_state: application_state(instance),
+            outcome: outcome.map(|o| o.to_str()),
+        })
+    }
+}
+
+impl EventSink for JsonReporter {
+    fn check_finished(&self, instance: &InstanceReport, execution: &Execution, _operation: Operation) {
+        self.execution(instance, execution, None)
+    }
+
+    /// Only applies, rollbacks and tests stream their output.  Checks and
+    /// deps print results which their own records already carry.
+    fn output_line(&self, id: &InstanceId, operation: Operation, line: &OutputLine) {
+        if !matches!(operation, Operation::Apply | Operation::Rollback | Operation::Test) {
+            return
+        }
+
+        self.emit(Record::Output {
+            unit: &id.name,
+            args: instance_args(id),
+            operation: operation.to_str(),
+            stream: line.stream.to_str(),
+            line: &line.text,
+            timestamp: line.timestamp.duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs_f64())
+                .unwrap_or(0.0),
+        })
+    }
+
+    fn execution_finished(&self, instance: &InstanceReport, execution: &Execution, outcome: Option<&Outcome>) {
+        self.execution(instance, execution, outcome)
+    }
+
+    fn execution_retrying(
+        &self,
+        id: &InstanceId,
+        execution: &Execution,
+        attempt: u32,
+        attempts: u32,
+        delay: Duration
+    ) {
+        self.emit(Record::Retry {
+            unit: &execution.unit_name,
+            args: instance_args(id),
+            operation: execution.operation.to_str(),
+            attempt,
+            attempts,
+            delay: delay.as_secs(),
+        })
+    }
+
+    fn execution_skipped(&self, instance: &InstanceReport, operation: Operation) {
+        self.emit(Record::Skipped {
+            unit: &instance.id.name,
+            args: instance_args(&instance.id),
+            operation: operation.to_str(),
+        })
+    }
+
+    fn plan(&self, instances: &[InstanceReport], operation: Operation) {
+        self.emit(Record::Plan {
+            operation: operation.to_str(),
+            units: instances.iter()
+                .map(|instance| PlannedUnit {
+                    unit: instance.id.name.clone(),
+                    args: instance_args(&instance.id),
+                    application_state: application_state(instance),
+                })
+                .collect(),
+        })
+    }
+
+    fn drift(&self, entries: &[DriftEntry]) {
+        for entry in entries.iter() {
+            self.emit(Record::Drift {
+                unit: &entry.unit,
+                args: json_table(&entry.args),
+                state: &entry.state,
+                reason: &entry.reason,
+            })
+        }
+    }
+
+    fn history(&self, entries: &[Entry]) {
+        for entry in entries.iter() {
+            self.emit(Record::History {
+                unit: &entry.unit,
+                args: json_table(&entry.args),
+                operation: &entry.operation,
+                result: &entry.result,
+                content_hash: &entry.content_hash,
+                timestamp: entry.timestamp.to_string(),
+                run_id: &entry.run_id,
+            })
+        }
+    }
+
+    fn summary(&self, summary: &Summary, operation: Operation) {
+        self.emit(Record::Summary {
+            operation: operation.to_str(),
+            exit_code: summary.exit_code(),
+            summary,
+        })
+    }
+
+    fn warning(&self, message: &str) {
+        self.emit(Record::Warning { message })
+    }
+
+    /// Errors go to stderr, as they do when printed as text
+    fn error(&self, error: &Error) {
+        eprintln!("{}", self.line(Record::Error { message: &error.msg }))
+    }
+}
+
+/// An instance's arguments as a JSON object
+pub(crate) fn instance_args(id: &InstanceId) -> Map<String, Value> {
+    id.args.vec.iter()
+        .map(|arg| (arg.name.clone(), json_value(&arg.value)))
+        .collect()
+}
+
+fn application_state(instance: &InstanceReport) -> Option<&'static str> {
+    match instance.application_state {
+        Some(ApplicationState::Applied) => Some("applied"),
+        Some(ApplicationState::NotApplied(_)) => Some("not applied"),
+        None => None
+    }
+}
+
+fn json_table(table: &toml::value::Table) -> Map<String, Value> {
+    table.iter()
+        .map(|(name, value)| (name.clone(), json_value(value)))
+        .collect()
+}
+
+/// TOML datetimes have no JSON counterpart, so they're given as strings
+fn json_value(value: &TomlValue) -> Value {
+    match value {
+        TomlValue::String(s) => Value::from(s.as_str()),
+        TomlValue::Integer(i) => Value::from(*i),
+        TomlValue::Float(f) => Value::from(*f),
+        TomlValue::Boolean(b) => Value::from(*b),
+        TomlValue::Datetime(d) => Value::from(d.to_string()),
+        TomlValue::Array(values) => Value::Array(values.iter().map(json_value).collect()),
+        TomlValue::Table(table) => Value::Object(json_table(table)),
+    }
+}
@@ -1,15 +1,27 @@
-use std::rc::Rc;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::fmt;
+use std::time::Duration;
 
 use crypto::sha1::Sha1;
 use crypto::digest::Digest;
+use toml::Value;
 
 use crate::error::Error;
+use crate::operation::Operation;
+use crate::execution::Execution;
+use crate::meta::DEFAULT_RETRY_BACKOFF;
 
 #[derive(Debug)]
 pub struct Definition {
     pub name: String,
     pub path: String,
     pub definition_type: DefinitionType,
+    /// Whether a directory unit ships a `./test` executable
+    pub has_test: bool,
+    pub timeouts: Timeouts,
+    /// Overrides the run's retry policy for this unit's apply
+    pub retry: Option<RetryPolicy>,
 }
 
 impl Definition {
@@ -17,26 +29,109 @@ impl Definition {
         Definition {
             name: name.to_string(),
             path: path.to_string(),
-            definition_type: definition_type,
+            definition_type,
+            has_test: false,
+            timeouts: Timeouts::default(),
+            retry: None,
         }
     }
 }
 
+/// Limits on how long a unit's operations may run, overriding the run's
+/// default
+#[derive(Debug, Default, Clone)]
+pub struct Timeouts {
+    /// Applies to any operation not listed in `operations`
+    pub all: Option<Duration>,
+    pub operations: HashMap<String, Duration>,
+}
+
+impl Timeouts {
+    pub fn get(&self, operation: Operation) -> Option<Duration> {
+        self.operations.get(operation.to_str()).cloned().or(self.all)
+    }
+}
+
+/// How many more times an apply is attempted when it fails, and how long to
+/// wait between attempts
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub count: u32,
+    /// Wait before the first retry, doubling for each one after it
+    pub backoff: Duration,
+    /// Exit codes worth retrying, any non-zero code if empty
+    pub exit_codes: Vec<i32>,
+}
+
+impl RetryPolicy {
+    pub fn none() -> RetryPolicy {
+        RetryPolicy { count: 0, backoff: Duration::from_secs(0), exit_codes: Vec::new() }
+    }
+
+    /// Retries any failure up to `count` times with the default backoff
+    pub fn new(count: u32) -> RetryPolicy {
+        RetryPolicy {
+            count,
+            backoff: Duration::from_secs(DEFAULT_RETRY_BACKOFF),
+            exit_codes: Vec::new(),
+        }
+    }
+
+    /// How long to wait after the given attempt, counting from 1
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.backoff * 2u32.saturating_pow(attempt.saturating_sub(1))
+    }
+}
+
 #[derive(Debug)]
 pub enum DefinitionType {
     Directory,
-    Executable
+    Executable,
+    /// A .toml file listing dependencies, which is never executed itself
+    Manifest
 }
 
-pub type DefinitionRc = Rc<Definition>;
+/// Definitions are shared with the worker threads which execute instances
+pub type DefinitionRef = Arc<Definition>;
 
 #[derive(Clone, Debug)]
 pub struct Argument {
     pub name: String,
-    pub value: String
+    pub value: Value
 }
 
-#[derive(Clone, Debug)]
+impl Argument {
+    /// The value as units receive it in their environment.  Strings are passed
+    /// as they are, other types in their TOML representation.
+    pub fn value_string(&self) -> String {
+        match &self.value {
+            Value::String(s) => s.to_string(),
+            other => inline_toml(other)
+        }
+    }
+}
+
+/// Renders a value as inline TOML, since tables would otherwise be rendered
+/// as a full document spanning several lines
+fn inline_toml(value: &Value) -> String {
+    match value {
+        Value::Table(table) => {
+            let pairs = table.iter()
+                .map(|(key, value)| format!("{} = {}", key, inline_toml(value)))
+                .collect::<Vec<String>>();
+
+            format!("{{ {} }}", pairs.join(", "))
+        },
+        Value::Array(values) => {
+            let items = values.iter().map(inline_toml).collect::<Vec<String>>();
+
+            format!("[{}]", items.join(", "))
+        },
+        other => other.to_string()
+    }
+}
+
+#[derive(Clone, Debug, Default)]
 pub struct ArgSet {
     pub vec: Vec<Argument>,
 }
@@ -62,33 +157,87 @@ impl ArgSet {
 
             let argument = Argument {
                 name: parts[0].to_string(),
-                value: parts[1].to_string()
+                value: Value::String(parts[1].to_string())
             };
 
             vec.push(argument)
         }
 
-        return Ok(ArgSet { vec: vec })
+        Ok(ArgSet { vec })
+    }
+
+    pub fn from_table(table: &toml::value::Table) -> ArgSet {
+        let vec = table.iter()
+            .map(|(name, value)| Argument { name: name.to_string(), value: value.clone() })
+            .collect();
+
+        ArgSet { vec }
+    }
+
+    /// Adds any of the given arguments which aren't already set
+    pub fn merge_defaults(&mut self, defaults: &ArgSet) {
+        for default in defaults.vec.iter() {
+            if !self.vec.iter().any(|arg| arg.name == default.name) {
+                self.vec.push(default.clone());
+            }
+        }
+    }
+
+    /// Renders the arguments as a TOML document of `name = value` pairs
+    pub fn to_toml(&self) -> Result<String, Error> {
+        let mut table = toml::value::Table::new();
+
+        for arg in self.vec.iter() {
+            table.insert(arg.name.clone(), arg.value.clone());
+        }
+
+        toml::to_string(&Value::Table(table)).map_err(|e|
+            wrap_error!("Could not render arguments as TOML: {}", e)
+        )
     }
 
+    /// Hashes the arguments in a canonical form: ordered by name, and with
+    /// each value's type, so that `port = 80` and `port = "80"` differ but the
+    /// order arguments were given in doesn't matter
     pub fn sha1(&self) -> String {
         let mut hasher = Sha1::new();
+        let mut args = self.vec.iter().collect::<Vec<&Argument>>();
+        args.sort_by(|a, b| a.name.cmp(&b.name));
 
-        for arg in self.vec.iter() {
-            hasher.input_str(&arg.name);
-            hasher.input_str(&arg.value);
+        for arg in args {
+            hasher.input_str(&format!("{}\0{}\0{}\0",
+                arg.name,
+                arg.value.type_str(),
+                inline_toml(&arg.value)));
         }
 
-        hasher.result_str().to_string()
+        hasher.result_str()
     }
 }
 
-#[derive(Debug)]
+impl fmt::Display for ArgSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pairs = self.vec.iter()
+            .map(|arg| format!("{}={}", arg.name, arg.value_string()))
+            .collect::<Vec<String>>();
+
+        write!(f, "{}", pairs.join(","))
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Instance {
-    pub definition_rc: DefinitionRc,
+    pub definition: DefinitionRef,
     pub run_state: RunState,
     pub id: InstanceId,
-    pub application_state: Option<ApplicationState>
+    pub application_state: Option<ApplicationState>,
+    pub outcome: Option<Outcome>,
+    /// Whether the unit's `./test` passed after it was applied
+    pub test_passed: Option<bool>,
+    /// Signatures of the instances this instance directly depends upon
+    pub dependencies: Vec<String>,
+    /// Every execution of the unit for this instance during the run
+    pub executions: Vec<Execution>
 }
 
 #[derive(Debug, Clone)]
@@ -104,18 +253,62 @@ pub enum ApplicationState {
     NotApplied(String)
 }
 
+/// Result of applying or rolling back an instance.  Convergence is confirmed by
+/// re-running its check afterwards.
+#[derive(Debug, Clone)]
+pub enum Outcome {
+    Converged,
+    NotConverged(String),
+    Failed(i32),
+    Skipped,
+    /// The apply, rollback or check ran for longer than its timeout allowed
+    TimedOut(Duration),
+    /// Converged during an apply which was then rolled back by `--atomic`
+    Reverted
+}
+
+impl Outcome {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            Outcome::Converged => "converged",
+            Outcome::NotConverged(_) => "not converged",
+            Outcome::Failed(_) => "failed",
+            Outcome::Skipped => "skipped",
+            Outcome::TimedOut(_) => "timed out",
+            Outcome::Reverted => "reverted",
+        }
+    }
+}
+
 impl Instance {
-    pub fn new(definition_rc: DefinitionRc, instance_id: InstanceId) -> Instance {
-        return Instance {
-            definition_rc: definition_rc,
+    pub fn new(definition: DefinitionRef, instance_id: InstanceId) -> Instance {
+        Instance {
+            definition,
             run_state: RunState::Init,
             id: instance_id,
-            application_state: None
+            application_state: None,
+            outcome: None,
+            test_passed: None,
+            dependencies: Vec::new(),
+            executions: Vec::new()
         }
     }
+
+    /// What executing the instance takes, without the record of its run
+    pub fn job(&self) -> Job {
+        Job { id: self.id.clone(), definition: Arc::clone(&self.definition) }
+    }
 }
 
-#[derive(Debug)]
+/// A unit and the arguments to execute it with, which is all that executors
+/// and the worker threads handing instances to them need
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub id: InstanceId,
+    pub definition: DefinitionRef,
+}
+
+#[derive(Debug, Clone)]
 pub struct InstanceId {
     pub name: String,
     pub args: ArgSet
@@ -124,14 +317,34 @@ pub struct InstanceId {
 impl InstanceId {
     pub fn build(name: &str, args_str: &str) -> Result<InstanceId, Error> {
         let args = ArgSet::parse(args_str)?;
-        Ok(InstanceId { name: name.to_string(), args: args })
+        Ok(InstanceId { name: name.to_string(), args })
+    }
+
+    /// Parses an instance from the `name` or `name:key=value,key=value` form
+    /// used for both dependency lines and root units given on the command line
+    pub fn parse(spec: &str) -> Result<InstanceId, Error> {
+        let parts = spec.split(":").collect::<Vec<&str>>();
+        match parts.len() {
+            1 => Ok(InstanceId::new(spec.to_string(), ArgSet::new())),
+            2 => InstanceId::build(parts[0], parts[1]),
+            _ => Err(Error::new(format!("Could not parse unit string: {}", spec)))
+        }
     }
 
     pub fn new(name: String, args: ArgSet) -> InstanceId {
-        InstanceId { name: name, args: args }
+        InstanceId { name, args }
     }
 
     pub fn signature(&self) -> String {
         format!("{}-{}", self.name, self.args.sha1()).to_string()
     }
 }
+
+impl fmt::Display for InstanceId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.args.vec.is_empty() {
+            true => write!(f, "{}", self.name),
+            false => write!(f, "{}({})", self.name, self.args)
+        }
+    }
+}
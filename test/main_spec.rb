@@ -1,20 +1,268 @@
 require 'serverspec'
 require 'pry'
+require 'json'
+require 'rexml/document'
 
 describe 'sysunit' do
   before :all do
     set :backend, :exec
   end
 
+  # Matches a line of a unit's output, streamed after the time it arrived
+  def streamed(label, text, stream: 1)
+    /^#{Regexp.escape(label)} \d\d:\d\d:\d\d #{stream}>#{Regexp.escape(text)}/
+  end
+
   it 'can run a unit' do
-    expect(command("sysunit apply hi").stdout.chomp).to eql("[hi|apply] hiiii!")
+    expect(command("sysunit apply hi").stdout.lines.first).to match(streamed("[hi|apply]", "hiiii!"))
+  end
+
+  it 'summarizes the run' do
+    result = command("sysunit apply apply")
+    expect(result.exit_status).to eql(3)
+    expect(result.stdout).to include(
+      "apply: 0 converged, 0 failed, 1 did not converge, 0 skipped")
+  end
+
+  it 'reports drift' do
+    result = command("sysunit drift apply")
+    expect(result.exit_status).to eql(2)
+    expect(result.stdout).to include('reason = "its not here my dude"')
+  end
+
+  it 'ends JSON reports of every operation with a summary' do
+    %w(check plan drift).each do |operation|
+      result = command("sysunit #{operation} hi -r json")
+      expect(result.stdout.lines.last).to include('"type":"summary"')
+    end
+  end
+
+  it 'only streams the output of applies, rollbacks and tests as JSON' do
+    result = command("sysunit apply args:name=json -r json")
+    expect(result.exit_status).to eql(0)
+
+    operations = result.stdout.lines
+      .map { |line| JSON.parse(line) }
+      .select { |record| record['type'] == 'output' }
+      .map { |record| record['operation'] }
+    expect(operations).to eql(['apply'])
+  end
+
+  it 'writes a JUnit report counting failed units' do
+    result = command("sysunit apply fail hi --report-file /tmp/sysunit-report.xml")
+    expect(result.exit_status).to eql(1)
+
+    report = REXML::Document.new(file('/tmp/sysunit-report.xml').content)
+    suite = REXML::XPath.first(report, '//testsuite')
+    expect(suite.attributes['tests']).to eql('2')
+    expect(suite.attributes['failures']).to eql('1')
+
+    cases = REXML::XPath.match(report, '//testcase')
+    expect(cases.map { |c| c.attributes['name'] }).to eql(['fail', 'hi'])
+
+    failures = REXML::XPath.match(report, '//testcase/failure')
+    expect(failures.length).to eql(1)
+    expect(failures.first.attributes['message']).to eql('apply exited with 1')
+    expect(failures.first.parent.attributes['name']).to eql('fail')
+  end
+
+  it 'rejects bare arguments after a unit given arguments with a colon' do
+    result = command("sysunit apply args:name=p name=q")
+    expect(result.exit_status).to eql(1)
+    expect(result.stdout).to include("`args:name=p` already has arguments")
+  end
+
+  it 'applies the dependencies of a manifest unit' do
+    result = command("sysunit apply manifest")
+    expect(result.exit_status).to eql(0)
+    expect(result.stdout).to match(streamed("[args(name=manifest)|apply]", "hi manifest"))
+  end
+
+  it 'runs the tests of directory units' do
+    result = command("sysunit test tested:name=spec")
+    expect(result.exit_status).to eql(0)
+    expect(result.stdout).to include("1 tests passed, 0 tests failed")
+  end
+
+  it 'documents units as markdown' do
+    result = command("sysunit doc dir --format markdown")
+    expect(result.exit_status).to eql(0)
+    expect(result.stdout).to start_with("# dir\n")
+    expect(result.stdout).to include(
+      "Example directory unit which carries a data file alongside it")
+    expect(result.stdout).to include("## Operations\n\n- `apply`\n- `deps`\n")
+  end
+
+  it 'reports the path of circular dependencies' do
+    result = command("sysunit check circ1")
+    expect(result.exit_status).to eql(1)
+    expect(result.stdout).to include(
+      "Circular dependency: circ1 -> circ2 -> circ1, reached from root unit circ1")
+  end
+
+  it 'rolls back dependents before their dependencies' do
+    command("sysunit apply deps")
+    result = command("sysunit rollback deps")
+    expect(result.exit_status).to eql(0)
+    expect(result.stdout).to match(/\[deps\|rollback\] deps removed\n.*\[hi\|rollback\] byeee!/m)
+  end
+
+  it 'leaves dependencies in place when rolling back with --root-only' do
+    command("sysunit apply deps")
+    result = command("sysunit rollback deps --root-only")
+    expect(result.exit_status).to eql(0)
+    expect(result.stdout).to match(streamed("[deps|rollback]", "deps removed"))
+    expect(result.stdout).not_to include("[hi|rollback]")
+    expect(file('/tmp/sysunit-hi')).to exist
+  end
+
+  it 'rolls back what it applied when a unit fails with --atomic' do
+    command("rm -f /tmp/sysunit-args-halfway")
+    result = command("sysunit apply halfway --atomic")
+    expect(result.exit_status).to eql(1)
+    expect(result.stdout).to match(streamed("[args(name=halfway)|apply]", "hi halfway"))
+    expect(result.stdout).to include("[args(name=halfway)|rollback]")
+    expect(result.stdout).to include("1 reverted")
+    expect(file('/tmp/sysunit-args-halfway')).not_to exist
+  end
+
+  it 'gives arguments from the command line precedence over the host, then the group' do
+    command("rm -f /tmp/sysunit-typed")
+    result = command("sysunit apply locals typed overridden=cli -i /sysunit/test/inventory.toml")
+    expect(result.exit_status).to eql(0)
+    expect(file('/tmp/sysunit-typed').content).to eql(<<~TOML)
+      from_group = "group"
+      from_host = "host"
+      overridden = "cli"
+      set_by = "host"
+    TOML
+  end
+
+  it 'keeps a journal only when given one' do
+    command("rm -f /tmp/sysunit-journal /tmp/sysunit-args-unjournaled /tmp/sysunit-args-journaled")
+    command("sysunit apply args name=unjournaled")
+    expect(file('/tmp/sysunit-journal')).not_to exist
+    command("sysunit apply args name=journaled --journal /tmp/sysunit-journal")
+    result = command("sysunit history args --journal /tmp/sysunit-journal")
+    expect(result.exit_status).to eql(0)
+    expect(result.stdout).to include("journaled")
+    expect(result.stdout).not_to include("unjournaled")
+  end
+
+  it 'exports the instance graph as JSON' do
+    result = command("sysunit graph deps --format json")
+    expect(result.exit_status).to eql(0)
+
+    graph = JSON.parse(result.stdout)
+    ids = graph['nodes'].map { |node| [node['unit'], node['id']] }.to_h
+    expect(ids.keys).to eql(['hi', 'deps'])
+    expect(graph['nodes'].map { |node| node['args'] }).to all(eql({}))
+    expect(graph['edges']).to eql([{ 'from' => ids['deps'], 'to' => ids['hi'] }])
+  end
+
+  describe 'scheduling units as a graph' do
+    before(:each) { command("rm -f /tmp/sysunit-slow-* /tmp/sysunit-hi") }
+
+    it 'runs independent units at once and skips the dependents of failures' do
+      started = Time.now
+      result = command("sysunit apply top -j 3")
+      expect(Time.now - started).to be < 4
+      expect(result.exit_status).to eql(1)
+      expect(result.stdout).to match(streamed("[slow(n=1)|apply]", "end"))
+      expect(result.stdout).to match(streamed("[slow(n=2)|apply]", "end"))
+      expect(result.stdout).to include("[top|apply] skipped due to an earlier failure")
+      expect(result.stdout).to include(
+        "apply: 3 converged, 1 failed, 0 did not converge, 1 skipped")
+    end
+
+    it 'skips everything not yet started after a failure with --fail-fast' do
+      result = command("sysunit apply top -j 3 --fail-fast")
+      expect(result.exit_status).to eql(1)
+      expect(result.stdout).to include("[hi|apply] skipped due to an earlier failure")
+      expect(result.stdout).to include("[top|apply] skipped due to an earlier failure")
+      expect(result.stdout).to include(
+        "apply: 2 converged, 1 failed, 0 did not converge, 2 skipped")
+    end
+
+    it 'waits for dependents before rolling back their dependencies' do
+      command("sysunit apply deps")
+      result = command("sysunit rollback deps -j 2")
+      expect(result.exit_status).to eql(0)
+      expect(result.stdout).to match(/\[deps\|rollback\] deps removed\n.*\[hi\|rollback\] byeee!/m)
+    end
+
+    it 'stops the run when a unit can not be executed' do
+      result = command("sysunit apply killed -j 2")
+      expect(result.exit_status).to eql(1)
+      expect(result.stdout).to include("[killed] killed by external signal")
+    end
+  end
+
+  describe 'timeouts' do
+    it 'kill units which run past --timeout along with everything they started' do
+      result = command("sysunit apply slow n=5 --timeout 1")
+      expect(result.exit_status).to eql(1)
+      expect(result.stdout).to include("timed out after 1s")
+      expect(result.stdout).to include("1 timed out")
+      expect(command("pgrep -f 'slee[p] 2'").exit_status).to eql(1)
+    end
+
+    it 'may be given per operation by units' do
+      started = Time.now
+      result = command("sysunit apply impatient")
+      expect(Time.now - started).to be < 4
+      expect(result.exit_status).to eql(1)
+      expect(result.stdout).to include("timed out after 1s")
+    end
+  end
+
+  describe 'retries' do
+    before(:each) { command("rm -f /tmp/sysunit-flaky /tmp/sysunit-flaky-tried") }
+
+    it 'attempts a failed apply again' do
+      result = command("sysunit apply flaky --retries 1 --retry-backoff 0")
+      expect(result.exit_status).to eql(0)
+      expect(result.stdout).to include("[flaky|apply] attempt 1 of 2 exited with 1, retrying in 0s")
+      expect(result.stdout).to match(streamed("[flaky|apply]", "second time lucky"))
+      expect(result.stdout).to include("apply: 1 converged, 0 failed")
+    end
+
+    it 'only retries the exit codes it is given' do
+      result = command("sysunit apply flaky --retries 1 --retry-backoff 0 --retry-exit-codes 2")
+      expect(result.exit_status).to eql(1)
+      expect(result.stdout).not_to include("retrying")
+    end
+  end
+
+  describe 'the arguments document' do
+    before(:each) { command("rm -f /tmp/sysunit-typed") }
+
+    it 'is given to units' do
+      result = command("sysunit apply typed name=local,port=80")
+      expect(result.exit_status).to eql(0)
+      expect(result.stdout).to match(streamed('[typed(name=local,port=80)|apply]', 'name = "local" port = "80"'))
+    end
+
+    it 'is passed on to units run by an adapter' do
+      result = command(
+        "SYSUNIT_ADAPTER_PATH=/sysunit/adapters sysunit apply typed name=adapter --adapter local.sh")
+      expect(result.exit_status).to eql(0)
+      expect(result.stdout).to match(streamed('[typed(name=adapter)|apply]', 'name = "adapter"'))
+    end
+
+    it 'is given to units over SSH' do
+      command("sysunit rollback -t ssh://root@ssh_host typed name=ssh")
+      result = command("sysunit apply -t ssh://root@ssh_host typed name=ssh")
+      expect(result.exit_status).to eql(0)
+      expect(result.stdout).to match(streamed('[typed(name=ssh)|apply]', 'name = "ssh"'))
+    end
   end
 
   describe 'the apk unit' do
     it 'installs the package' do
       result = command('sysunit apply apk_install package_name=python3')
       expect(result.exit_status).to eql(0)
-      expect(result.stdout.chomp).to eql("[apk_install|apply] installed python3")
+      expect(result.stdout).to match(streamed("[apk_install(package_name=python3)|apply]", "installed python3"))
       expect(command("which python3").stdout.chomp).to eql('/usr/bin/python3')
     end
   end
@@ -23,7 +271,39 @@ describe 'sysunit' do
     it 'can run against a remote host' do
       result = sysunit_apply("args name=bob")
       expect(result.exit_status).to eql(0)
-      expect(result.stdout.chomp).to eql("[args|apply] hi bob")
+      expect(result.stdout.lines.first).to match(streamed("[args(name=bob)|apply]", "hi bob"))
+    end
+
+    it 'can run against several hosts at once' do
+      result = command(
+        "sysunit apply -t ssh://root@ssh_host -t local://localhost --parallel-hosts 2 args name=fleet")
+      expect(result.exit_status).to eql(0)
+      expect(result.stdout).to match(streamed("[ssh_host] [args(name=fleet)|apply]", "hi fleet"))
+      expect(result.stdout).to match(streamed("[localhost] [args(name=fleet)|apply]", "hi fleet"))
+      expect(result.stdout).to match(
+        /^host +converged +failed +not converged +timed out +skipped +tests failed +status$/)
+      expect(result.stdout).to match(/^ssh_host +1 +0 +0 +0 +0 +0 +ok$/)
+    end
+
+    it 'runs instances of the same unit at once over several connections' do
+      command("sysunit rollback -t ssh://root@ssh_host slow:n=3 slow:n=4")
+      started = Time.now
+      result = command("sysunit apply -t ssh://root@ssh_host -j 2 slow:n=3 slow:n=4")
+      expect(Time.now - started).to be < 4
+      expect(result.exit_status).to eql(0)
+      expect(result.stdout).to match(streamed("[slow(n=3)|apply]", "end"))
+      expect(result.stdout).to match(streamed("[slow(n=4)|apply]", "end"))
+      expect(result.stdout).to include("apply: 2 converged, 0 failed")
+    end
+
+    it 'kills units on the remote host when they time out' do
+      result = command("sysunit apply -t ssh://root@ssh_host slow n=6 --timeout 1")
+      expect(result.exit_status).to eql(1)
+      expect(result.stdout).to include("timed out after 1s")
+      remaining = command(
+        "ssh -o StrictHostKeyChecking=no -o UserKnownHostsFile=/dev/null root@ssh_host " \
+        "\"pgrep -f 'slee[p] 2'\"")
+      expect(remaining.exit_status).to eql(1)
     end
 
     private
@@ -0,0 +1,98 @@
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use sysunit::{ApplicationState, Builder, Operation, Outcome};
+use tempfile::TempDir;
+
+/// Writes an executable unit into the directory
+fn write_unit(dir: &Path, name: &str, script: &str) {
+    let path = dir.join(name);
+    fs::write(&path, script).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+/// A directory of units: `marker` creates the file it's given, `broken`
+/// depends upon a marker and fails to apply
+fn units_dir() -> TempDir {
+    let dir = tempfile::tempdir().unwrap();
+
+    write_unit(dir.path(), "marker", "#!/bin/sh\n\
+        case \"$1\" in\n\
+        check) [ -f \"$path\" ] && echo ok;;\n\
+        apply) touch \"$path\";;\n\
+        rollback) rm \"$path\";;\n\
+        esac\n\
+        exit 0\n");
+
+    write_unit(dir.path(), "broken", "#!/bin/sh\n\
+        case \"$1\" in\n\
+        deps) echo \"marker:path=$path\";;\n\
+        apply) echo nope >&2; exit 4;;\n\
+        esac\n\
+        exit 0\n");
+
+    dir
+}
+
+fn builder(operation: Operation, units: &TempDir) -> Builder {
+    Builder::new(operation).unit_paths(vec![units.path().to_str().unwrap().to_string()])
+}
+
+#[test]
+fn applies_a_unit_and_reports_its_executions() {
+    let units = units_dir();
+    let path = units.path().join("applied");
+
+    let plan = builder(Operation::Apply, &units)
+        .unit(&format!("marker:path={}", path.display()))
+        .resolve()
+        .unwrap();
+
+    let instances = plan.instances();
+    assert_eq!(instances.len(), 1);
+    assert_eq!(instances[0].id.name, "marker");
+    assert!(instances[0].application_state.is_none());
+
+    let report = plan.run().unwrap();
+    assert_eq!(report.exit_code(), 0);
+    assert!(path.exists());
+
+    let instance = &report.instances[0];
+    assert!(matches!(instance.outcome, Some(Outcome::Converged)));
+    assert!(matches!(instance.application_state, Some(ApplicationState::Applied)));
+
+    let operations = instance.executions.iter()
+        .map(|execution| execution.operation.to_str())
+        .collect::<Vec<&str>>();
+    assert_eq!(operations, vec!["check", "apply", "check"]);
+    assert_eq!(report.summary.converged, vec![instance.id.to_string()]);
+}
+
+#[test]
+fn reports_a_failure_after_applying_dependencies() {
+    let units = units_dir();
+    let path = units.path().join("dependency");
+
+    let plan = builder(Operation::Apply, &units)
+        .unit(&format!("broken:path={}", path.display()))
+        .resolve()
+        .unwrap();
+
+    let names = plan.instances().into_iter().map(|i| i.id.name).collect::<Vec<String>>();
+    assert_eq!(names, vec!["marker", "broken"]);
+
+    let report = plan.run().unwrap();
+    assert_eq!(report.exit_code(), 1);
+    assert!(path.exists());
+    assert!(matches!(report.instances[0].outcome, Some(Outcome::Converged)));
+    assert!(matches!(report.instances[1].outcome, Some(Outcome::Failed(4))));
+    assert_eq!(report.summary.failed.len(), 1);
+}
+
+#[test]
+fn rejects_operations_which_can_not_be_planned() {
+    let units = units_dir();
+
+    assert!(builder(Operation::Doc, &units).unit("marker").resolve().is_err());
+}